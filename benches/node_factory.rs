@@ -0,0 +1,99 @@
+//! Compares the per-node memory and allocation cost of the node factories.
+//!
+//! Allocations are counted during the first round. The arena allocates a chunk for every 1024 nodes,
+//! and releases it once its nodes are dropped.
+//!
+//! Run with `cargo bench --bench node_factory`.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::hint::black_box;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::time::Duration;
+use std::time::Instant;
+
+use linked::list::ArenaNodeFactory;
+use linked::list::BoxNodeFactory;
+use linked::list::LinkedList;
+use linked::list::NodeFactory;
+use linked::list::RcNodeFactory;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const NODES: usize = 1_000_000;
+const ROUNDS: u32 = 10;
+
+struct Report {
+    bytes_per_node: usize,
+    allocations_per_node: f64,
+    push: Duration,
+    drop: Duration,
+}
+
+fn run<F: NodeFactory<Value = u64>>() -> Report {
+    let mut report = Report {
+        bytes_per_node: 0,
+        allocations_per_node: 0.0,
+        push: Duration::ZERO,
+        drop: Duration::ZERO,
+    };
+    for round in 0..ROUNDS {
+        let list = LinkedList::<u64, F>::new();
+        let mut handles = Vec::with_capacity(NODES);
+
+        let allocations = ALLOCATIONS.load(Relaxed);
+        let bytes = ALLOCATED_BYTES.load(Relaxed);
+        let start = Instant::now();
+        for i in 0..NODES {
+            handles.push(list.push_back(i as u64));
+        }
+        report.push += start.elapsed();
+        if round == 0 {
+            let allocations = ALLOCATIONS.load(Relaxed) - allocations;
+            report.allocations_per_node = allocations as f64 / NODES as f64;
+            report.bytes_per_node = (ALLOCATED_BYTES.load(Relaxed) - bytes) / NODES;
+        }
+
+        black_box(&list);
+        let start = Instant::now();
+        drop(handles);
+        report.drop += start.elapsed();
+    }
+    report.push /= ROUNDS;
+    report.drop /= ROUNDS;
+    report
+}
+
+fn print(name: &str, report: Report) {
+    println!(
+        "{name:<17} {:>5} B/node {:>7.4} alloc/node {:>10.2?} push {:>10.2?} drop",
+        report.bytes_per_node, report.allocations_per_node, report.push, report.drop
+    );
+}
+
+fn main() {
+    println!("{NODES} nodes of u64, average of {ROUNDS} rounds");
+    print("RcNodeFactory", run::<RcNodeFactory<u64>>());
+    print("BoxNodeFactory", run::<BoxNodeFactory<u64>>());
+    print("ArenaNodeFactory", run::<ArenaNodeFactory<u64>>());
+}
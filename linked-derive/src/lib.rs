@@ -0,0 +1,99 @@
+//! Derive macros for the `linked` crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse_macro_input;
+use syn::Attribute;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Error;
+use syn::Member;
+
+/// Implements `linked::collectible::Collectible` for a struct.
+///
+/// The field marked `#[collectible(handle)]` is a `linked::collectible::Slot` that keeps the handle
+/// of the item once it is collected, whatever the container. It should be skipped by serde, and
+/// ignored by `Eq` and `Hash` for the elements of a set.
+///
+/// ```
+/// use linked::collectible::Slot;
+/// use linked::list::LinkedList;
+/// use linked::Collectible;
+///
+/// #[derive(Collectible, serde::Deserialize)]
+/// struct Task {
+///     name: String,
+///     #[collectible(handle)]
+///     #[serde(skip)]
+///     handle: Slot,
+/// }
+///
+/// let tasks: LinkedList<Task> = serde_json::from_str(r#"[{"name": "a"}]"#).unwrap();
+/// assert_eq!("a", tasks.current().unwrap().name);
+/// ```
+#[proc_macro_derive(Collectible, attributes(collectible))]
+pub fn derive_collectible(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Collectible can only be derived for structs",
+        ));
+    };
+
+    if let Some(attr) = collectible(&input.attrs).next() {
+        return Err(Error::new_spanned(
+            attr,
+            "`#[collectible(handle)]` goes on the field that keeps the handle",
+        ));
+    }
+
+    let mut handle = None;
+    for (index, field) in data.fields.iter().enumerate() {
+        for attr in collectible(&field.attrs) {
+            attr.parse_nested_meta(|meta| match meta.path.is_ident("handle") {
+                true => Ok(()),
+                false => Err(meta.error("expected `handle`")),
+            })?;
+            if handle.is_some() {
+                return Err(Error::new_spanned(
+                    attr,
+                    "Only one field can keep the handle",
+                ));
+            }
+            handle = Some(match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(index.into()),
+            });
+        }
+    }
+    let Some(handle) = handle else {
+        return Err(Error::new(
+            Span::call_site(),
+            "Collectible needs a field marked `#[collectible(handle)]`",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::linked::collectible::Collectible for #name #ty_generics #where_clause {
+            fn slot(&self) -> &::linked::collectible::Slot {
+                &self.#handle
+            }
+        }
+    })
+}
+
+fn collectible(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("collectible"))
+}
@@ -0,0 +1,84 @@
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use linked::collectible::Slot;
+use linked::hash_map::LinkedHashMap;
+use linked::hash_map::LinkedHashSet;
+use linked::list::LinkedList;
+use linked::Collectible;
+use serde::Deserialize;
+
+#[derive(Collectible, Deserialize)]
+struct Task {
+    name: String,
+    #[collectible(handle)]
+    #[serde(skip)]
+    handle: Slot,
+}
+
+#[derive(Collectible, Deserialize)]
+struct Account<T> {
+    balance: T,
+    #[collectible(handle)]
+    #[serde(skip)]
+    handle: Slot,
+}
+
+#[derive(Collectible, Deserialize, Clone)]
+struct Tag(
+    String,
+    #[collectible(handle)]
+    #[serde(skip)]
+    Slot,
+);
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Tag {}
+
+impl Hash for Tag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+#[test]
+fn list() {
+    let tasks: LinkedList<Task> = serde_json::from_str(r#"[{"name":"a"},{"name":"b"}]"#).unwrap();
+    let names = |tasks: &LinkedList<Task>| tasks.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+    assert_eq!(vec!["a", "b"], names(&tasks));
+
+    // Each task owns its node.
+    let task = tasks.current().unwrap();
+    drop(tasks.take_handle(&task).unwrap());
+    assert_eq!(vec!["b"], names(&tasks));
+}
+
+#[test]
+fn map() {
+    let json = r#"[["x",{"balance":1}],["y",{"balance":2}]]"#;
+    let accounts: LinkedHashMap<String, Account<u32>> = serde_json::from_str(json).unwrap();
+    let balances = |accounts: &LinkedHashMap<String, Account<u32>>| {
+        let mut balances = Vec::new();
+        accounts.for_each(|_, a| balances.push(a.balance));
+        balances
+    };
+    assert_eq!(vec![1, 2], balances(&accounts));
+
+    // Accounts are not `Clone`: their handles are taken by key.
+    drop(accounts.take_handle("x").unwrap());
+    assert_eq!(vec![2], balances(&accounts));
+}
+
+#[test]
+fn set() {
+    let tags: LinkedHashSet<Tag> = serde_json::from_str(r#"[["a"],["b"]]"#).unwrap();
+    assert_eq!(vec!["a", "b"], tags.iter().map(|t| t.0).collect::<Vec<_>>());
+
+    drop(tags.take_handle(&Tag("b".into(), Slot::new())).unwrap());
+    assert_eq!(vec!["a"], tags.iter().map(|t| t.0).collect::<Vec<_>>());
+}
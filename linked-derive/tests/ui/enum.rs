@@ -0,0 +1,8 @@
+use linked::Collectible;
+
+#[derive(Collectible)]
+enum Task {
+    A,
+}
+
+fn main() {}
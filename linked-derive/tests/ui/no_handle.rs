@@ -0,0 +1,8 @@
+use linked::Collectible;
+
+#[derive(Collectible)]
+struct Task {
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use linked::collectible::Slot;
+use linked::Collectible;
+
+#[derive(Collectible)]
+struct Task {
+    #[collectible(handle)]
+    a: Slot,
+    #[collectible(handle)]
+    b: Slot,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use linked::collectible::Slot;
+use linked::Collectible;
+
+#[derive(Collectible)]
+#[collectible(vec)]
+struct Task {
+    #[collectible(handle)]
+    handle: Slot,
+}
+
+fn main() {}
@@ -0,0 +1,220 @@
+//! Items that own their entry once collected into a container.
+//!
+//! Collecting items into a [LinkedList](crate::list::LinkedList),
+//! [LinkedHashMap](crate::hash_map::LinkedHashMap) or
+//! [LinkedHashSet](crate::hash_map::LinkedHashSet), with [Extend], [FromIterator] or serde, gives
+//! the handle of each entry to its item, through [Collectible::slot]. The entry is then removed
+//! once the handle is taken out of the slot and dropped.
+//!
+//! Containers give the handle back by key or by item, with `take_handle`, and
+//! [list::Handle::take_from](crate::list::Handle::take_from) or
+//! [hash_map::Handle::take_from](crate::hash_map::Handle::take_from) take it out of a slot.
+//!
+//! [CollectibleValue] makes any value collectible, and `#[derive(Collectible)]` makes a struct
+//! with a [Slot] field collectible.
+//!
+//! A slot keeps its handle boxed, whatever the type of its container. Only containers whose
+//! keys, values and node factories are `'static` can therefore be collected from items.
+
+use alloc::boxed::Box;
+use core::any::Any;
+use core::cell::Cell;
+use core::fmt;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+#[cfg(feature = "derive")]
+pub use linked_derive::Collectible;
+
+/// An item that keeps the handle of its entry once collected into a container.
+pub trait Collectible {
+    fn slot(&self) -> &Slot;
+}
+
+/// Keeps the handle of a collected item, whatever its container.
+///
+/// Cloning a slot gives an empty slot: the handle stays with the item that was collected.
+#[derive(Default)]
+pub struct Slot(Cell<Option<Box<dyn Any>>>);
+
+/// Makes any value [Collectible].
+///
+/// Comparisons, hashing and serialization only consider the value, so that the wrapper can be
+/// used as the key of a set.
+#[derive(Default)]
+pub struct CollectibleValue<V> {
+    slot: Slot,
+    value: V,
+}
+
+impl Slot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps `handle`, and drops the handle it kept before.
+    pub(crate) fn register<H: 'static>(&self, handle: H) {
+        self.0.set(Some(Box::new(handle)))
+    }
+
+    /// Takes the handle out of the slot if it is an `H`: dropping it removes the item from its
+    /// container.
+    pub(crate) fn take<H: 'static>(&self) -> Option<H> {
+        let handle = self.0.take()?;
+        match handle.downcast() {
+            Ok(handle) => Some(*handle),
+            Err(handle) => {
+                self.0.set(Some(handle));
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let handle = self.0.take();
+        let is_empty = handle.is_none();
+        self.0.set(handle);
+        is_empty
+    }
+}
+
+impl Clone for Slot {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_empty() {
+            true => f.write_str("Slot(empty)"),
+            false => f.write_str("Slot(collected)"),
+        }
+    }
+}
+
+impl<V> Collectible for CollectibleValue<V> {
+    fn slot(&self) -> &Slot {
+        &self.slot
+    }
+}
+
+impl<V> From<V> for CollectibleValue<V> {
+    fn from(value: V) -> Self {
+        Self {
+            slot: Slot::new(),
+            value,
+        }
+    }
+}
+
+impl<V> Deref for CollectibleValue<V> {
+    type Target = V;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<V> DerefMut for CollectibleValue<V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<V> Clone for CollectibleValue<V>
+where
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        Self::from(self.value.clone())
+    }
+}
+
+impl<V> fmt::Debug for CollectibleValue<V>
+where
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CollectibleValue")
+            .field(&self.value)
+            .finish()
+    }
+}
+
+impl<V> PartialEq for CollectibleValue<V>
+where
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<V> Eq for CollectibleValue<V> where V: Eq {}
+
+impl<V> Hash for CollectibleValue<V>
+where
+    V: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<V> serde::Serialize for CollectibleValue<V>
+where
+    V: serde::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.value.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, V> serde::Deserialize<'de> for CollectibleValue<V>
+where
+    V: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        V::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Collectible;
+    use super::CollectibleValue;
+    use super::Slot;
+
+    #[test]
+    fn slot() {
+        let slot = Slot::new();
+        assert!(slot.is_empty());
+        slot.register(1u32);
+        assert_eq!(None, slot.take::<u64>());
+        assert!(!slot.is_empty());
+        assert!(slot.clone().is_empty());
+        assert_eq!(Some(1), slot.take::<u32>());
+        assert!(slot.is_empty());
+    }
+
+    #[test]
+    fn value() {
+        let value = CollectibleValue::from("a".to_string());
+        value.slot().register(());
+        let copy = value.clone();
+        assert_eq!(value, copy);
+        assert!(copy.slot().is_empty());
+        assert_eq!(r#"CollectibleValue("a")"#, format!("{value:?}"));
+    }
+}
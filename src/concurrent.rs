@@ -0,0 +1,288 @@
+//! Concurrent containers for high-contention workloads.
+//!
+//! Model checking runs with `RUSTFLAGS="--cfg loom" cargo test --release --lib concurrent`.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::hash::Hash;
+#[cfg(not(loom))]
+use std::sync::Arc;
+
+#[cfg(loom)]
+use loom::sync::Arc;
+
+use crate::sync::hash_map::InsertResult;
+use crate::sync::hash_map::LinkedHashMap;
+
+/// A set split into shards, each with its own lock, so that writers of different shards do not
+/// contend.
+///
+/// Elements are ordered by insertion within their shard. Iteration visits the shards in turn, and
+/// each shard is copied while it is locked: a concurrent writer may change a shard that was not
+/// visited yet.
+///
+/// Like in [LinkedHashSet](crate::hash_map::LinkedHashSet), elements are removed when their last
+/// [Handle](crate::sync::hash_map::Handle) is dropped, on any thread. Clones share the same set.
+pub struct LinkedHashSet<K, S = RandomState>
+where
+    K: Clone + Eq + Hash,
+{
+    shards: Arc<Shards<K, S>>,
+}
+
+struct Shards<K, S>
+where
+    K: Clone + Eq + Hash,
+{
+    hasher: S,
+    shards: Box<[LinkedHashMap<K, ()>]>,
+}
+
+impl<K> LinkedHashSet<K>
+where
+    K: Clone + Eq + Hash,
+{
+    /// Creates a set with 4 shards per available core.
+    pub fn new() -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(4 * cores)
+    }
+
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+
+impl<K, S> LinkedHashSet<K, S>
+where
+    K: Clone + Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a set whose elements are assigned to shards by `hasher`.
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        assert!(shards > 0, "A set needs at least one shard");
+        Self {
+            shards: Arc::new(Shards {
+                hasher,
+                shards: (0..shards).map(|_| LinkedHashMap::new()).collect(),
+            }),
+        }
+    }
+
+    fn shard<Q>(&self, key: &Q) -> &LinkedHashMap<K, ()>
+    where
+        Q: Hash + ?Sized,
+    {
+        let Shards { hasher, shards } = &*self.shards;
+        &shards[hasher.hash_one(key) as usize % shards.len()]
+    }
+
+    pub fn insert(&self, key: K) -> InsertResult<K, (), bool> {
+        let InsertResult { previous, handle } = self.shard(&key).insert(key, ());
+        InsertResult {
+            previous: previous.is_some(),
+            handle,
+        }
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.shard(key).contains_key(key)
+    }
+
+    /// Iterates over a snapshot of each shard in turn.
+    pub fn iter(&self) -> impl Iterator<Item = K> + '_ {
+        self.shards.shards.iter().flat_map(|shard| shard.keys())
+    }
+
+    /// Removes the elements for which `keep` returns false, one shard at a time. See
+    /// [LinkedHashMap::retain](crate::hash_map::LinkedHashMap::retain).
+    pub fn retain(&self, mut keep: impl FnMut(&K) -> bool) {
+        for shard in self.shards.shards.iter() {
+            shard.retain(|k, ()| keep(k))
+        }
+    }
+
+    /// Removes all the elements, one shard at a time.
+    pub fn clear(&self) {
+        for shard in self.shards.shards.iter() {
+            shard.clear()
+        }
+    }
+}
+
+impl<K> Default for LinkedHashSet<K>
+where
+    K: Clone + Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, S> Clone for LinkedHashSet<K, S>
+where
+    K: Clone + Eq + Hash,
+{
+    fn clone(&self) -> Self {
+        Self {
+            shards: self.shards.clone(),
+        }
+    }
+}
+
+impl<K, S> std::fmt::Debug for LinkedHashSet<K, S>
+where
+    K: std::fmt::Debug + Clone + Eq + Hash,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::mpsc;
+
+    use super::LinkedHashSet;
+
+    #[test]
+    fn threads() {
+        let set = LinkedHashSet::<u32>::with_shards(8);
+        let (sender, receiver) = mpsc::channel();
+        let threads = (0..8)
+            .map(|t| {
+                let set = set.clone();
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    let mut handles = vec![];
+                    for i in 0..1000 {
+                        let key = t * 1000 + i;
+                        let handle = set.insert(key).handle;
+                        assert!(set.contains(&key));
+                        // Keep one element out of ten.
+                        if i % 10 == 0 {
+                            handles.push(handle);
+                        }
+                    }
+                    sender.send(handles).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(sender);
+        threads.into_iter().for_each(|t| t.join().unwrap());
+
+        let handles = receiver.into_iter().flatten().collect::<Vec<_>>();
+        let expected = (0..8000).filter(|k| k % 10 == 0).collect::<HashSet<_>>();
+        assert_eq!(expected, set.iter().collect::<HashSet<_>>());
+
+        // Within a shard, elements keep their insertion order.
+        let shard = set.shards.shards.iter().find(|s| s.keys().count() > 1);
+        let keys = shard.unwrap().keys().filter(|k| k / 1000 == 0);
+        assert!(keys.collect::<Vec<_>>().is_sorted());
+
+        set.retain(|k| k % 20 == 0);
+        assert_eq!(400, set.iter().count());
+        drop(handles);
+        assert_eq!(0, set.iter().count());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use std::hash::BuildHasherDefault;
+    use std::hash::DefaultHasher;
+
+    use loom::thread;
+
+    use super::LinkedHashSet;
+
+    // Executions must be deterministic, so shards are not picked by a random hasher.
+    type Set = LinkedHashSet<u32, BuildHasherDefault<DefaultHasher>>;
+
+    #[test]
+    fn insert_and_drop() {
+        loom::model(|| {
+            let set = Set::with_shards_and_hasher(2, Default::default());
+            let a = set.insert(1).handle;
+            let threads = [2, 3].map(|key| {
+                let set = set.clone();
+                thread::spawn(move || {
+                    let handle = set.insert(key).handle;
+                    assert!(set.contains(&key));
+                    handle
+                })
+            });
+            let [b, c] = threads.map(|t| t.join().unwrap());
+
+            let mut keys = set.iter().collect::<Vec<_>>();
+            keys.sort();
+            assert_eq!(vec![1, 2, 3], keys);
+
+            // Handles are dropped concurrently, on other threads than the one that inserted them.
+            let threads = [a, b].map(|handle| thread::spawn(move || drop(handle)));
+            threads.into_iter().for_each(|t| t.join().unwrap());
+            assert_eq!(vec![3], set.iter().collect::<Vec<_>>());
+            drop(c);
+            assert!(!set.contains(&3));
+        });
+    }
+
+    #[test]
+    fn contend_for_shard() {
+        loom::model(|| {
+            let set = Set::with_shards_and_hasher(1, Default::default());
+            let a = set.insert(1).handle;
+
+            // Both threads remove the same element of the only shard, one by dropping its handle
+            // and the other with `retain`, while an element is inserted.
+            let dropper = {
+                let set = set.clone();
+                thread::spawn(move || {
+                    drop(a);
+                    assert!(!set.contains(&1));
+                })
+            };
+            let inserter = {
+                let set = set.clone();
+                thread::spawn(move || {
+                    let b = set.insert(2).handle;
+                    set.retain(|k| *k != 1);
+                    assert_eq!(vec![2], set.iter().collect::<Vec<_>>());
+                    b
+                })
+            };
+            dropper.join().unwrap();
+            let b = inserter.join().unwrap();
+
+            assert_eq!(vec![2], set.iter().collect::<Vec<_>>());
+            drop(b);
+            assert_eq!(0, set.iter().count());
+        });
+    }
+
+    #[test]
+    fn insert_same_key() {
+        loom::model(|| {
+            let set = Set::with_shards_and_hasher(1, Default::default());
+            let threads = [(); 2].map(|()| {
+                let set = set.clone();
+                thread::spawn(move || set.insert(1))
+            });
+            let [a, b] = threads.map(|t| t.join().unwrap());
+            // Exactly one insertion found the element already there.
+            assert!(a.previous != b.previous);
+
+            drop(a.handle);
+            assert!(set.contains(&1));
+            drop(b.handle);
+            assert!(!set.contains(&1));
+        });
+    }
+}
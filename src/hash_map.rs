@@ -1,16 +1,34 @@
-use std::borrow::Borrow;
-use std::cell::RefCell;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cell::Cell;
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::hash::Hash;
+#[cfg(feature = "std")]
 use std::collections::hash_map;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
-use std::hash::Hash;
-use std::rc::Rc;
 
+#[cfg(not(feature = "std"))]
+use hashbrown::hash_map;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+
+use self::subscription::Event;
+use self::subscription::Subscribers;
 use super::list;
 use super::list::LinkedList;
-use crate::list::RcNodeFactory;
+use crate::list::BoxNodeFactory;
 
-pub mod collectible;
+mod collectible;
+#[cfg(feature = "std")]
+pub mod journal;
+#[cfg(feature = "serde")]
 pub mod serializable;
+pub mod subscription;
 
 pub struct LinkedHashMap<K, V>
 where
@@ -18,6 +36,7 @@ where
 {
     keys: LinkedList<K>,
     map: Rc<RefCell<HashMap<K, LinkedHashMapValue<K, V>>>>,
+    subscribers: Subscribers<K, V>,
 }
 
 pub struct LinkedHashSet<K>
@@ -32,33 +51,63 @@ where
     K: Clone + Eq + Hash,
 {
     value: V,
-    handle: std::rc::Weak<HandleImpl<K, V>>,
+    handle: alloc::rc::Weak<HandleImpl<K, V>>,
 }
 
-#[derive(Clone)]
 pub struct Handle<K, V>(#[allow(unused)] Rc<HandleImpl<K, V>>)
 where
     K: Clone + Eq + Hash;
 
+impl<K, V> Clone for Handle<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V> Handle<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    /// The address that identifies the handle and its clones.
+    #[cfg(all(feature = "serde", feature = "std"))]
+    pub(crate) fn address(&self) -> *const () {
+        Rc::as_ptr(&self.0).cast()
+    }
+}
+
 struct HandleImpl<K, V>
 where
     K: Clone + Eq + Hash,
 {
     linked_hash_map: LinkedHashMap<K, V>,
-    key_handle: RefCell<Option<list::Handle<RcNodeFactory<K>>>>,
+    key_handle: RefCell<Option<list::Handle<BoxNodeFactory<K>>>>,
+    on_remove: Cell<Option<OnRemove<K, V>>>,
 }
 
+type OnRemove<K, V> = Box<dyn FnOnce(K, V)>;
+
+type DetachedEntry<K, V> = (K, V, Option<OnRemove<K, V>>);
+
 impl<K, V> Drop for HandleImpl<K, V>
 where
     K: Clone + Eq + Hash,
 {
     fn drop(&mut self) {
-        static EXPECT_MSG: &'static str =
-            "Handles can never have an empty value. Using Option<V> so they are Default";
-        let key = self.key_handle.take().map(|k| k.value().clone());
-        let key = key.expect(EXPECT_MSG);
+        static EXPECT_MSG: &str = "Attached handles always have an entry in the map";
+
+        // Handles are detached when their entry was removed by a bulk operation.
+        let Some(key) = self.key_handle.take().map(|k| k.value().clone()) else {
+            return;
+        };
         let removed = self.linked_hash_map.map.borrow_mut().remove(&key);
-        removed.expect(EXPECT_MSG);
+        let value = removed.expect(EXPECT_MSG).value;
+        let (key, value) = self.linked_hash_map.notify_removed(key, value);
+        if let Some(on_remove) = self.on_remove.take() {
+            on_remove(key, value);
+        }
     }
 }
 
@@ -91,42 +140,215 @@ where
         Self {
             keys: Default::default(),
             map: Default::default(),
+            subscribers: Default::default(),
+        }
+    }
+
+    /// Creates a map that holds at least `capacity` entries before its hash table reallocates.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            keys: Default::default(),
+            map: Rc::new(RefCell::new(HashMap::with_capacity(capacity))),
+            subscribers: Default::default(),
         }
     }
 
     pub fn insert(&self, key: K, value: V) -> InsertResult<K, V, Option<V>> {
-        match self.map.borrow_mut().entry(key.clone()) {
+        self.insert_and_notify(key, value, None)
+    }
+
+    /// Like [LinkedHashMap::insert], but `on_remove` receives the key and the value once the entry
+    /// is removed because its last [Handle] was dropped.
+    ///
+    /// Inserting the same key again replaces the callback: the previous value is returned to the
+    /// caller, so its callback is discarded.
+    pub fn insert_with_on_remove(
+        &self,
+        key: K,
+        value: V,
+        on_remove: impl FnOnce(K, V) + 'static,
+    ) -> InsertResult<K, V, Option<V>> {
+        self.insert_and_notify(key, value, Some(Box::new(on_remove)))
+    }
+
+    fn insert_and_notify(
+        &self,
+        key: K,
+        value: V,
+        on_remove: Option<OnRemove<K, V>>,
+    ) -> InsertResult<K, V, Option<V>> {
+        let event_key = self.has_subscribers().then(|| key.clone());
+        let result = self.insert_impl(key, value, on_remove);
+        if let Some(key) = event_key {
+            if result.previous.is_some() {
+                self.notify(&Event::Replaced { key: key.clone() });
+                self.notify(&Event::Moved { key });
+            } else {
+                self.notify(&Event::Inserted { key });
+            }
+        }
+        result
+    }
+
+    fn insert_impl(
+        &self,
+        key: K,
+        value: V,
+        on_remove: Option<OnRemove<K, V>>,
+    ) -> InsertResult<K, V, Option<V>> {
+        let mut map = self.map.borrow_mut();
+        let (result, replaced) = match map.entry(key.clone()) {
             hash_map::Entry::Occupied(mut entry) => {
                 let old = entry.get_mut();
                 let key_handle = self.keys.push_back(key);
-                let previous = std::mem::replace(&mut old.value, value);
+                let previous = core::mem::replace(&mut old.value, value);
                 let handle = old.handle.upgrade().unwrap();
-                *handle.key_handle.borrow_mut() = Some(key_handle);
-                InsertResult {
+                let replaced = (
+                    handle.key_handle.replace(Some(key_handle)),
+                    handle.on_remove.replace(on_remove),
+                );
+                let result = InsertResult {
                     previous: Some(previous),
                     handle: Handle(handle),
-                }
+                };
+                (result, Some(replaced))
             }
             hash_map::Entry::Vacant(entry) => {
                 let key_handle = self.keys.push_back(key);
                 let handle = Rc::new(HandleImpl {
                     linked_hash_map: self.clone(),
                     key_handle: RefCell::new(Some(key_handle)),
+                    on_remove: Cell::new(on_remove),
                 });
                 let value = LinkedHashMapValue {
                     value,
                     handle: Rc::downgrade(&handle),
                 };
                 entry.insert(value);
-                InsertResult {
+                let result = InsertResult {
                     previous: None,
                     handle: Handle(handle),
-                }
+                };
+                (result, None)
+            }
+        };
+        // The replaced callback may own handles to entries of this map: drop it once the map is
+        // released.
+        drop(map);
+        drop(replaced);
+        result
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.borrow_map().contains_key(key)
+    }
+
+    /// Removes the entries for which `keep` returns false.
+    ///
+    /// The [Handle]s of removed entries are detached: dropping them no longer removes anything.
+    /// Removal callbacks and subscribers are notified as if the handles had been dropped.
+    ///
+    /// `keep` runs while the map is borrowed, so it must not access the map.
+    pub fn retain(&self, mut keep: impl FnMut(&K, &mut V) -> bool) {
+        for (key, value, on_remove) in self.detach_where(|k, v| !keep(k, v)) {
+            let (key, value) = self.notify_removed(key, value);
+            if let Some(on_remove) = on_remove {
+                on_remove(key, value);
+            }
+        }
+    }
+
+    /// Removes all the entries, detaching their [Handle]s. See [LinkedHashMap::retain].
+    pub fn clear(&self) {
+        self.retain(|_, _| false)
+    }
+
+    /// Removes all the entries, detaching their [Handle]s, and returns them in order.
+    ///
+    /// Subscribers are notified, but removal callbacks are not called since the caller takes
+    /// ownership of the entries.
+    pub fn drain(&self) -> impl Iterator<Item = (K, V)> {
+        let drained = self.detach_where(|_, _| true);
+        let drained = drained
+            .into_iter()
+            .map(|(key, value, _)| self.notify_removed(key, value))
+            .collect::<Vec<_>>();
+        drained.into_iter()
+    }
+
+    /// Sorts the entries in place. Handles stay valid, and the sort is stable.
+    ///
+    /// `compare` runs while the map is borrowed, so it must not modify the map.
+    pub fn sort_by(&self, mut compare: impl FnMut((&K, &V), (&K, &V)) -> Ordering) {
+        {
+            let map = self.borrow_map();
+            self.keys
+                .sort_by(|a, b| compare((a, &map[a].value), (b, &map[b].value)));
+        }
+        if self.has_subscribers() {
+            self.notify(&Event::Reordered);
+        }
+    }
+
+    /// Sorts the entries in place by key. See [LinkedHashMap::sort_by].
+    pub fn sort_keys(&self)
+    where
+        K: Ord,
+    {
+        self.sort_by(|(a, _), (b, _)| a.cmp(b))
+    }
+
+    /// Calls `f` on each entry in order, borrowing the entries instead of cloning them.
+    ///
+    /// `f` runs while the map is borrowed, so it must not modify the map.
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        let map = self.borrow_map();
+        for key in self.keys.iter() {
+            f(&key, &map[&*key].value)
+        }
+    }
+
+    /// Like [LinkedHashMap::for_each], with the address that identifies the [Handle] of each entry.
+    #[cfg(all(feature = "serde", feature = "std"))]
+    pub(crate) fn for_each_with_address(&self, mut f: impl FnMut(&K, &V, *const ())) {
+        let map = self.borrow_map();
+        for key in self.keys.iter() {
+            let entry = &map[&*key];
+            f(&key, &entry.value, entry.handle.as_ptr().cast())
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.borrow_map().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn detach_where(&self, mut remove: impl FnMut(&K, &mut V) -> bool) -> Vec<DetachedEntry<K, V>> {
+        static EXPECT_MSG: &str = "Keys in the list always have an entry in the map";
+        let keys = self.keys.iter().map(|k| (*k).clone()).collect::<Vec<_>>();
+        let mut map = self.map.borrow_mut();
+        let mut removed = vec![];
+        for key in keys {
+            let entry = map.get_mut(&key).expect(EXPECT_MSG);
+            if !remove(&key, &mut entry.value) {
+                continue;
             }
+            let entry = map.remove(&key).expect(EXPECT_MSG);
+            let handle = entry.handle.upgrade().expect(EXPECT_MSG);
+            handle.key_handle.take();
+            removed.push((key, entry.value, handle.on_remove.take()));
         }
+        removed
     }
 
-    fn borrow_map(&self) -> std::cell::Ref<HashMap<K, LinkedHashMapValue<K, V>>> {
+    fn borrow_map(&self) -> core::cell::Ref<'_, HashMap<K, LinkedHashMapValue<K, V>>> {
         RefCell::borrow(&self.map)
     }
 }
@@ -159,6 +381,52 @@ where
             (key, value)
         })
     }
+
+    /// Returns the entry at position `index` in the iteration order. This is O(n).
+    pub fn get_index(&self, index: usize) -> Option<(K, V)> {
+        let key = self.keys.iter().nth(index)?.clone();
+        let value = self.get(&key).unwrap();
+        Some((key, value))
+    }
+
+    /// Returns the position of `key` in the iteration order. This is O(n).
+    pub fn index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.keys.iter().position(|k| (*k).borrow() == key)
+    }
+
+    pub fn first(&self) -> Option<(K, V)> {
+        let key = self.keys.current()?.clone();
+        let value = self.get(&key).unwrap();
+        Some((key, value))
+    }
+
+    pub fn last(&self) -> Option<(K, V)> {
+        let key = self.keys.last()?.clone();
+        let value = self.get(&key).unwrap();
+        Some((key, value))
+    }
+
+    /// Iterates over the entries that follow `key`, or nothing if `key` is not in the map.
+    pub fn iter_from<Q>(&self, key: &Q) -> impl Iterator<Item = (K, V)> + '_
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let handle = self.borrow_map().get(key).and_then(|v| v.handle.upgrade());
+        let keys = handle.map(|handle| {
+            let key_handle = handle.key_handle.borrow();
+            self.keys.iter_after(key_handle.as_ref().unwrap())
+        });
+        keys.into_iter().flatten().map(|key| {
+            let key = (*key).clone();
+            let value = self.get::<K>(&key).unwrap();
+            (key, value)
+        })
+    }
 }
 
 impl<K, V> Default for LinkedHashMap<K, V>
@@ -181,12 +449,19 @@ where
         }
     }
 
+    /// See [LinkedHashMap::with_capacity].
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            linked_hash_map: LinkedHashMap::with_capacity(capacity),
+        }
+    }
+
     pub fn contains<Q>(&self, key: &Q) -> bool
     where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        self.linked_hash_map.borrow_map().contains_key(key)
+        self.linked_hash_map.contains_key(key)
     }
 
     pub fn insert(&self, key: K) -> InsertResult<K, (), bool> {
@@ -196,6 +471,72 @@ where
     pub fn iter(&self) -> impl Iterator<Item = K> {
         self.linked_hash_map.keys()
     }
+
+    /// Calls `f` on each element in order, borrowing the elements instead of cloning them.
+    ///
+    /// `f` runs while the set is borrowed, so it must not modify the set.
+    pub fn for_each(&self, mut f: impl FnMut(&K)) {
+        self.linked_hash_map.for_each(|k, _| f(k))
+    }
+
+    /// Elements of `self`, followed by the elements of `other` that are not in `self`.
+    pub fn union<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = K> + 'a {
+        self.iter().chain(other.difference(self))
+    }
+
+    /// Elements of `self` that are also in `other`.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = K> + 'a {
+        self.iter().filter(|key| other.contains(key))
+    }
+
+    /// Elements of `self` that are not in `other`.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = K> + 'a {
+        self.iter().filter(|key| !other.contains(key))
+    }
+
+    /// Elements of `self` that are not in `other`, followed by the elements of `other` that are
+    /// not in `self`.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = K> + 'a {
+        self.difference(other).chain(other.difference(self))
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.linked_hash_map.borrow_map().len() <= other.linked_hash_map.borrow_map().len()
+            && self.iter().all(|key| other.contains(&key))
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).next().is_none()
+    }
+
+    /// Sorts the elements in place. See [LinkedHashMap::sort_by].
+    pub fn sort_by(&self, mut compare: impl FnMut(&K, &K) -> Ordering) {
+        self.linked_hash_map
+            .sort_by(|(a, ()), (b, ())| compare(a, b))
+    }
+
+    /// Sorts the elements in place. See [LinkedHashMap::sort_keys].
+    pub fn sort(&self)
+    where
+        K: Ord,
+    {
+        self.linked_hash_map.sort_keys()
+    }
+
+    /// Removes the elements for which `keep` returns false. See [LinkedHashMap::retain].
+    pub fn retain(&self, mut keep: impl FnMut(&K) -> bool) {
+        self.linked_hash_map.retain(|k, _| keep(k))
+    }
+
+    /// Removes all the elements. See [LinkedHashMap::clear].
+    pub fn clear(&self) {
+        self.linked_hash_map.clear()
+    }
+
+    /// Removes all the elements and returns them in order. See [LinkedHashMap::drain].
+    pub fn drain(&self) -> impl Iterator<Item = K> {
+        self.linked_hash_map.drain().map(|(k, ())| k)
+    }
 }
 
 impl<K> Default for LinkedHashSet<K>
@@ -209,21 +550,21 @@ where
     }
 }
 
-impl<K, V> std::fmt::Debug for LinkedHashMap<K, V>
+impl<K, V> core::fmt::Debug for LinkedHashMap<K, V>
 where
-    K: std::fmt::Debug + Clone + Eq + Hash,
-    V: std::fmt::Debug + Clone,
+    K: core::fmt::Debug + Clone + Eq + Hash,
+    V: core::fmt::Debug + Clone,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_map().entries(self.iter()).finish()
     }
 }
 
-impl<K> std::fmt::Debug for LinkedHashSet<K>
+impl<K> core::fmt::Debug for LinkedHashSet<K>
 where
-    K: std::fmt::Debug + Clone + Eq + Hash,
+    K: core::fmt::Debug + Clone + Eq + Hash,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_set().entries(self.iter()).finish()
     }
 }
@@ -236,6 +577,7 @@ where
         Self {
             keys: self.keys.clone(),
             map: self.map.clone(),
+            subscribers: self.subscribers.clone(),
         }
     }
 }
@@ -251,6 +593,72 @@ where
     }
 }
 
+impl<K, V> LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: PartialEq,
+{
+    /// Compares the entries of both maps, ignoring their order.
+    pub fn eq_unordered(&self, other: &Self) -> bool {
+        let (a, b) = (self.borrow_map(), other.borrow_map());
+        a.len() == b.len()
+            && a.iter()
+                .all(|(key, a)| b.get(key).map(|b| a.value == b.value).unwrap_or(false))
+    }
+}
+
+impl<K, V> PartialEq for LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.borrow_map(), other.borrow_map());
+        if a.len() != b.len() {
+            return false;
+        }
+
+        for (ka, kb) in self.keys.iter().zip(other.keys.iter()) {
+            if *ka != *kb || a[&*ka].value != b[&*kb].value {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<K, V> Eq for LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Eq,
+{
+}
+
+impl<K, V> Hash for LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Hash,
+{
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        let map = self.borrow_map();
+        state.write_usize(map.len());
+        for key in self.keys.iter() {
+            key.hash(state);
+            map[&*key].value.hash(state);
+        }
+    }
+}
+
+impl<K> LinkedHashSet<K>
+where
+    K: Clone + Eq + Hash,
+{
+    /// Compares the elements of both sets, ignoring their order.
+    pub fn eq_unordered(&self, other: &Self) -> bool {
+        self.linked_hash_map.eq_unordered(&other.linked_hash_map)
+    }
+}
+
 impl<K> PartialEq for LinkedHashSet<K>
 where
     K: Clone + Eq + Hash,
@@ -265,17 +673,33 @@ where
                 return false;
             }
         }
-        return true;
+        true
+    }
+}
+
+impl<K> Eq for LinkedHashSet<K> where K: Clone + Eq + Hash {}
+
+impl<K> Hash for LinkedHashSet<K>
+where
+    K: Clone + Eq + Hash,
+{
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.linked_hash_map.hash(state);
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::collectible::key::CollectibleKey;
-    use super::collectible::value::CollectibleValue;
     use super::LinkedHashMap;
     use super::LinkedHashSet;
+    use crate::collectible::CollectibleValue;
+
+    fn set(keys: &[&str]) -> LinkedHashSet<CollectibleValue<String>> {
+        keys.iter()
+            .map(|k| CollectibleValue::from(k.to_string()))
+            .collect()
+    }
 
     #[test]
     fn linked_hash_map() {
@@ -315,13 +739,52 @@ mod tests {
 
         assert!(matches!(map.get("c"), Some(789)));
         drop(c);
-        assert!(matches!(map.get("c"), None));
+        assert!(map.get("c").is_none());
         assert_eq!(
             vec![("b".to_string(), 456), ("a".to_string(), 124)],
             map.iter().collect::<Vec<_>>()
         );
     }
 
+    #[test]
+    fn on_remove() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let removed = Rc::new(RefCell::new(vec![]));
+        let on_remove = {
+            let removed = removed.clone();
+            move |k: String, v: i64| removed.borrow_mut().push((k, v))
+        };
+        let map: LinkedHashMap<String, i64> = LinkedHashMap::default();
+        let a = map.insert_with_on_remove("a".into(), 1, on_remove.clone());
+        let b = map.insert_with_on_remove("b".into(), 2, on_remove.clone());
+        let c = map.insert_with_on_remove("c".into(), 3, on_remove);
+
+        drop(a);
+        assert_eq!(vec![("a".to_string(), 1)], *removed.borrow());
+
+        assert_eq!(Some(2), map.insert("b".into(), 4).previous);
+        drop(b);
+        assert_eq!(1, removed.borrow().len());
+        assert_eq!(vec![("c".to_string(), 3)], map.iter().collect::<Vec<_>>());
+
+        drop(c);
+        assert_eq!(
+            vec![("a".to_string(), 1), ("c".to_string(), 3)],
+            *removed.borrow()
+        );
+    }
+
+    #[test]
+    fn replace_on_remove() {
+        let map = LinkedHashMap::<String, i64>::new();
+        let b = map.insert("b".into(), 1).handle;
+        let _a = map.insert_with_on_remove("a".into(), 1, move |_, _| drop(b));
+        let _a = map.insert("a".into(), 2).handle;
+        assert_eq!(vec![("a".to_string(), 2)], map.iter().collect::<Vec<_>>());
+    }
+
     #[test]
     fn linked_hash_set() {
         let set: LinkedHashSet<String> = LinkedHashSet::new();
@@ -352,7 +815,7 @@ mod tests {
     #[test]
     fn collect_set() {
         let set = [
-            CollectibleKey::from("a".to_string()),
+            CollectibleValue::from("a".to_string()),
             "b".to_string().into(),
             "c".to_string().into(),
         ]
@@ -396,44 +859,364 @@ mod tests {
         assert_eq!(None, map.get("aa").map(|v| *v));
     }
 
+    #[test]
+    fn collected_handles() {
+        let map = [
+            ("a".to_string(), CollectibleValue::from(1)),
+            ("b".to_string(), 2.into()),
+        ]
+        .into_iter()
+        .collect::<LinkedHashMap<_, _>>();
+        drop(map.take_handle("a").unwrap());
+        assert!(map.take_handle("a").is_none());
+        assert_eq!(vec!["b".to_string()], map.keys().collect::<Vec<_>>());
+
+        let set = set(&["a", "b"]);
+        drop(
+            set.take_handle(&CollectibleValue::from("b".to_string()))
+                .unwrap(),
+        );
+        assert_eq!(
+            vec!["a".to_string()],
+            set.iter().map(|k| k.to_string()).collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn contains() {
         let set = LinkedHashSet::<String>::new();
         let _a = set.insert("a".into()).handle;
         let b = set.insert("b".into()).handle;
         let _c = set.insert("c".into()).handle;
-        assert_eq!(true, set.contains("a"));
-        assert_eq!(true, set.contains("b"));
-        assert_eq!(true, set.contains("c"));
+        assert!(set.contains("a"));
+        assert!(set.contains("b"));
+        assert!(set.contains("c"));
         drop(b);
-        assert_eq!(true, set.contains("a"));
-        assert_eq!(false, set.contains("b"));
-        assert_eq!(true, set.contains("c"));
-        assert_eq!(false, set.contains("aa"));
+        assert!(set.contains("a"));
+        assert!(!set.contains("b"));
+        assert!(set.contains("c"));
+        assert!(!set.contains("aa"));
+    }
+
+    #[test]
+    fn map_eq() {
+        use core::hash::BuildHasher;
+        use std::hash::RandomState;
+
+        let m1 = LinkedHashMap::<String, i32>::new();
+        let m2 = LinkedHashMap::<String, i32>::new();
+        let _a1 = m1.insert("a".into(), 1);
+        let _b1 = m1.insert("b".into(), 2);
+        let _b2 = m2.insert("b".into(), 2);
+        let _a2 = m2.insert("a".into(), 1);
+        assert_ne!(m1, m2);
+        assert!(m1.eq_unordered(&m2));
+
+        let _a2 = m2.insert("a".into(), 3);
+        assert!(!m1.eq_unordered(&m2));
+        let _a1 = m1.insert("a".into(), 3);
+        assert_eq!(m1, m2);
+
+        let hasher = RandomState::new();
+        assert_eq!(hasher.hash_one(&m1), hasher.hash_one(&m2));
+        let _c2 = m2.insert("c".into(), 4);
+        assert_ne!(hasher.hash_one(&m1), hasher.hash_one(&m2));
+    }
+
+    #[test]
+    fn set_eq_unordered() {
+        let s1 = set(&["a", "b", "c"]);
+        let s2 = set(&["c", "b", "a"]);
+        let s3 = set(&["c", "b"]);
+        assert_ne!(s1, s2);
+        assert!(s1.eq_unordered(&s2));
+        assert!(!s1.eq_unordered(&s3));
+        assert!(!s3.eq_unordered(&s1));
+    }
+
+    #[test]
+    fn set_algebra() {
+        let s1 = set(&["a", "b", "c", "d"]);
+        let s2 = set(&["e", "d", "b"]);
+        let s3 = set(&["x", "y"]);
+        let to_vec = |i: &mut dyn Iterator<Item = CollectibleValue<String>>| {
+            i.map(|k| k.to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["a", "b", "c", "d", "e"], to_vec(&mut s1.union(&s2)));
+        assert_eq!(vec!["e", "d", "b", "a", "c"], to_vec(&mut s2.union(&s1)));
+        assert_eq!(vec!["b", "d"], to_vec(&mut s1.intersection(&s2)));
+        assert_eq!(vec!["d", "b"], to_vec(&mut s2.intersection(&s1)));
+        assert_eq!(vec!["a", "c"], to_vec(&mut s1.difference(&s2)));
+        assert_eq!(vec!["e"], to_vec(&mut s2.difference(&s1)));
+        assert_eq!(
+            vec!["a", "c", "e"],
+            to_vec(&mut s1.symmetric_difference(&s2))
+        );
+
+        let inter = set(&["d", "b"]);
+        assert!(inter.is_subset(&s1));
+        assert!(inter.is_subset(&s2));
+        assert!(!s1.is_subset(&s2));
+        assert!(!s1.is_disjoint(&s2));
+        assert!(s1.is_disjoint(&s3));
     }
 
     #[test]
+    fn positional() {
+        let map = LinkedHashMap::<String, i32>::new();
+        assert_eq!(None, map.first());
+        assert_eq!(None, map.last());
+        let _a = map.insert("a".into(), 1);
+        let _b = map.insert("b".into(), 2);
+        let _c = map.insert("c".into(), 3);
+
+        assert_eq!(Some(("a".to_string(), 1)), map.first());
+        assert_eq!(Some(("c".to_string(), 3)), map.last());
+        assert_eq!(Some(("b".to_string(), 2)), map.get_index(1));
+        assert_eq!(None, map.get_index(3));
+        assert_eq!(Some(2), map.index_of("c"));
+        assert_eq!(None, map.index_of("d"));
+
+        assert_eq!(
+            vec![("b".to_string(), 2), ("c".to_string(), 3)],
+            map.iter_from("a").collect::<Vec<_>>()
+        );
+        assert_eq!(0, map.iter_from("c").count());
+        assert_eq!(0, map.iter_from("d").count());
+
+        let _a = map.insert("a".into(), 4);
+        assert_eq!(
+            vec![("c".to_string(), 3), ("a".to_string(), 4)],
+            map.iter_from("b").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn retain() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let removed = Rc::new(RefCell::new(vec![]));
+        let map = LinkedHashMap::<String, i32>::new();
+        let a = map.insert_with_on_remove("a".into(), 1, {
+            let removed = removed.clone();
+            move |k, v| removed.borrow_mut().push((k, v))
+        });
+        let b = map.insert("b".into(), 2);
+        let c = map.insert("c".into(), 3);
+        map.retain(|k, v| {
+            *v *= 10;
+            k != "a"
+        });
+        assert_eq!(vec![("a".to_string(), 10)], *removed.borrow());
+        assert_eq!(
+            vec![("b".to_string(), 20), ("c".to_string(), 30)],
+            map.iter().collect::<Vec<_>>()
+        );
+
+        // Detached handles don't remove anything, even if the key is inserted again.
+        let a2 = map.insert("a".into(), 4);
+        drop(a);
+        assert_eq!(Some(4), map.get("a"));
+        assert_eq!(1, removed.borrow().len());
+
+        drop(b);
+        drop(c);
+        drop(a2);
+        assert_eq!(0, map.iter().count());
+    }
+
+    #[test]
+    fn clear_and_drain() {
+        let map = LinkedHashMap::<String, i32>::new();
+        let a = map.insert("a".into(), 1);
+        let b = map.insert("b".into(), 2);
+        assert_eq!(
+            vec![("a".to_string(), 1), ("b".to_string(), 2)],
+            map.drain().collect::<Vec<_>>()
+        );
+        assert_eq!(0, map.iter().count());
+
+        let c = map.insert("c".into(), 3);
+        map.clear();
+        assert_eq!(0, map.iter().count());
+        drop(a);
+        drop(b);
+        drop(c);
+
+        let set = LinkedHashSet::<String>::new();
+        let _a = set.insert("a".into());
+        let _b = set.insert("b".into());
+        let _c = set.insert("c".into());
+        set.retain(|k| k != "b");
+        assert_eq!(vec!["a", "c"], set.iter().collect::<Vec<_>>());
+        assert_eq!(vec!["a", "c"], set.drain().collect::<Vec<_>>());
+        assert!(!set.contains("a"));
+    }
+
+    #[test]
+    fn sort() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let map = LinkedHashMap::<String, i32>::new();
+        let a = map.insert("a".into(), 3);
+        let _b = map.insert("b".into(), 1);
+        let _c = map.insert("c".into(), 2);
+        let events = Rc::new(RefCell::new(vec![]));
+        let _subscription = map.subscribe({
+            let events = events.clone();
+            move |event| events.borrow_mut().push(format!("{event:?}"))
+        });
+
+        map.sort_by(|(_, a), (_, b)| a.cmp(b));
+        assert_eq!(vec!["b", "c", "a"], map.keys().collect::<Vec<_>>());
+        assert_eq!(vec!["Reordered"], *events.borrow());
+
+        map.sort_keys();
+        assert_eq!(vec!["a", "b", "c"], map.keys().collect::<Vec<_>>());
+        drop(a);
+        assert_eq!(vec!["b", "c"], map.keys().collect::<Vec<_>>());
+
+        let set = LinkedHashSet::<String>::new();
+        let _x = set.insert("x".into());
+        let _y = set.insert("yy".into());
+        let _z = set.insert("z".into());
+        set.sort_by(|a, b| b.len().cmp(&a.len()));
+        assert_eq!(vec!["yy", "x", "z"], set.iter().collect::<Vec<_>>());
+        set.sort();
+        assert_eq!(vec!["x", "yy", "z"], set.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
     fn serde_map() {
-        let map: LinkedHashMap<String, CollectibleValue<String, i32>> =
+        let map: LinkedHashMap<String, CollectibleValue<i32>> =
             serde_json::from_str(r#"[["a",1],["b",2],["c",3]]"#).unwrap();
         assert_eq!(
             r#"[["a",1],["b",2],["c",3]]"#,
             serde_json::to_string(&map).unwrap()
         );
+
+        let bytes = postcard::to_allocvec(&map).unwrap();
+        let entries: Vec<(String, i32)> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            vec![("a".into(), 1), ("b".into(), 2), ("c".into(), 3)],
+            entries
+        );
+
+        let invalid = r#"[["a",1],["b"]]"#;
+        assert!(
+            serde_json::from_str::<LinkedHashMap<String, CollectibleValue<i32>>>(invalid).is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_as_map() {
+        use super::serializable::as_map;
+
+        let json = r#"{"b":2,"a":1,"c":3}"#;
+        let map: LinkedHashMap<String, CollectibleValue<i32>> =
+            as_map::deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+        assert_eq!(vec!["b", "a", "c"], map.keys().collect::<Vec<_>>());
+
+        let mut output = vec![];
+        as_map::serialize(&map, &mut serde_json::Serializer::new(&mut output)).unwrap();
+        assert_eq!(json, String::from_utf8(output).unwrap());
+
+        struct AsMap<'t>(&'t LinkedHashMap<String, CollectibleValue<i32>>);
+        impl serde::Serialize for AsMap<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                as_map::serialize(self.0, serializer)
+            }
+        }
+        let bytes = postcard::to_allocvec(&AsMap(&map)).unwrap();
+        let entries: Vec<(String, i32)> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            vec![("b".into(), 2), ("a".into(), 1), ("c".into(), 3)],
+            entries
+        );
+
+        let pairs = r#"[["b",2]]"#;
+        let error = as_map::deserialize::<String, CollectibleValue<i32>, _>(
+            &mut serde_json::Deserializer::from_str(pairs),
+        );
+        assert!(error.is_err());
     }
 
     #[test]
+    #[cfg(feature = "serde")]
+    fn serde_as_map_with_handles() {
+        use super::serializable::as_map;
+        use super::serializable::MapWithHandles;
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Config {
+            #[serde(with = "as_map::with_handles")]
+            limits: MapWithHandles<String, u32>,
+        }
+
+        let json = r#"{"limits":{"b":2,"a":1}}"#;
+        let Config { limits } = serde_json::from_str(json).unwrap();
+        let MapWithHandles(map, mut handles) = limits;
+        assert_eq!(
+            vec![("b".to_string(), 2), ("a".to_string(), 1)],
+            map.iter().collect::<Vec<_>>()
+        );
+
+        drop(handles.remove(0));
+        let limits = MapWithHandles(map, handles);
+        let json = serde_json::to_string(&Config { limits }).unwrap();
+        assert_eq!(r#"{"limits":{"a":1}}"#, json);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_with_handles() {
+        use super::serializable::MapWithHandles;
+        use super::serializable::SetWithHandles;
+
+        let MapWithHandles(map, mut handles) =
+            serde_json::from_str::<MapWithHandles<String, i32>>(r#"[["a",1],["b",2],["c",3]]"#)
+                .unwrap();
+        assert_eq!(
+            r#"[["a",1],["b",2],["c",3]]"#,
+            serde_json::to_string(&map).unwrap()
+        );
+        assert_eq!(3, handles.len());
+        handles.remove(1);
+        assert_eq!(r#"[["a",1],["c",3]]"#, serde_json::to_string(&map).unwrap());
+        drop(handles);
+        assert_eq!(None, map.first());
+
+        let SetWithHandles(set, handles) =
+            serde_json::from_str::<SetWithHandles<String>>(r#"["a","b","a"]"#).unwrap();
+        assert_eq!(r#"["b","a"]"#, serde_json::to_string(&set).unwrap());
+        assert_eq!(3, handles.len());
+        drop(handles);
+        assert_eq!(0, set.iter().count());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
     fn serde_set() {
-        let set: LinkedHashSet<CollectibleKey<String>> =
+        let set: LinkedHashSet<CollectibleValue<String>> =
             serde_json::from_str(r#"["a","b","c"]"#).unwrap();
         assert_eq!(r#"["a","b","c"]"#, serde_json::to_string(&set).unwrap());
+
+        let bytes = postcard::to_allocvec(&set).unwrap();
+        let keys: Vec<String> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(vec!["a", "b", "c"], keys);
     }
 
     #[test]
+    #[cfg(feature = "serde")]
     fn eq() {
-        let s1: LinkedHashSet<CollectibleKey<String>> =
+        let s1: LinkedHashSet<CollectibleValue<String>> =
             serde_json::from_str(r#"["a","b","c"]"#).unwrap();
-        let s2: LinkedHashSet<CollectibleKey<String>> =
+        let s2: LinkedHashSet<CollectibleValue<String>> =
             serde_json::from_str(r#"["a","b","c"]"#).unwrap();
         assert_eq!(r#"["a","b","c"]"#, serde_json::to_string(&s1).unwrap());
         assert_eq!(r#"["a","b","c"]"#, serde_json::to_string(&s2).unwrap());
@@ -0,0 +1,628 @@
+//! A write-ahead log of the changes made to a [LinkedHashMap], to rebuild it after a crash.
+//!
+//! A journal starts with a header and a snapshot of the map: one insert record per entry, closed by
+//! a snapshot record. One record follows per insertion, removal or reordering. Each record is
+//! framed by its length and the CRC-32 checksum of that length, then holds a tag, length-prefixed
+//! fields and the CRC-32 checksum of the tag and fields. Checksums are verified before fields are
+//! read, so that only a record cut short at the end of the journal is taken for a crash.
+//!
+//! Records are at most 4 GiB long: a change that does not fit is a write error.
+
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+use core::hash::Hash;
+use core::num::TryFromIntError;
+use std::collections::HashMap;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+
+use super::subscription::Event;
+use super::subscription::Subscription;
+use super::Handle;
+use super::LinkedHashMap;
+use crate::snapshot;
+use crate::snapshot::Codec;
+
+pub const MAGIC: [u8; 4] = *b"LNKJ";
+pub const VERSION: u16 = 2;
+
+const INSERT: u8 = 0;
+const REMOVE: u8 = 1;
+const ORDER: u8 = 2;
+const SNAPSHOT: u8 = 3;
+
+/// Appends a record to a writer for every change made to a map. See [LinkedHashMap::journal].
+///
+/// The map stops being journaled once the journal is dropped.
+pub struct Journal<K, V, W>
+where
+    K: Clone + Eq + Hash,
+{
+    state: Rc<RefCell<State<K, V, W>>>,
+    _subscription: Subscription<K, V>,
+}
+
+struct State<K, V, W>
+where
+    K: Clone + Eq + Hash,
+{
+    map: LinkedHashMap<K, V>,
+    sink: Sink<W>,
+    key_codec: Box<dyn Codec<K>>,
+    value_codec: Box<dyn Codec<V>>,
+    compaction: Option<Compaction<W>>,
+}
+
+/// Compacts the journal once it holds more than `ratio` records per entry of the map.
+struct Compaction<W> {
+    ratio: usize,
+    writers: Box<dyn FnMut() -> io::Result<W>>,
+}
+
+struct Sink<W> {
+    writer: W,
+    records: usize,
+    /// The first error returned by the writer, or the first record too long to be written. No
+    /// record is written after it.
+    error: Option<io::Error>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Invalid(snapshot::Error),
+    /// The record at the given index has a valid checksum but an unknown tag.
+    UnknownRecord {
+        tag: u8,
+        record: u64,
+    },
+}
+
+impl<K, V> LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    /// Starts journaling the changes made to the map into `writer`, after a snapshot of its
+    /// current entries.
+    ///
+    /// Records are written by a subscriber: subscribers registered earlier run first, so changes
+    /// they make to the map are recorded before the change that triggered them. Write errors are
+    /// reported by [Journal::flush].
+    pub fn journal<W>(
+        &self,
+        writer: W,
+        key_codec: impl Codec<K> + 'static,
+        value_codec: impl Codec<V> + 'static,
+    ) -> io::Result<Journal<K, V, W>>
+    where
+        K: 'static,
+        V: 'static,
+        W: Write + 'static,
+    {
+        let (key_codec, value_codec) = (Box::new(key_codec), Box::new(value_codec));
+        let sink =
+            Sink::start(writer, self, &*key_codec, &*value_codec).map_err(|(_, error)| error)?;
+        let state = Rc::new(RefCell::new(State {
+            map: self.clone(),
+            sink,
+            key_codec,
+            value_codec,
+            compaction: None,
+        }));
+        let subscription = self.subscribe({
+            let state = state.clone();
+            move |event| state.borrow_mut().record(event)
+        });
+        Ok(Journal {
+            state,
+            _subscription: subscription,
+        })
+    }
+
+    /// Rebuilds a map from a journal. The entries are owned by the returned handles.
+    ///
+    /// A record cut short by a crash at the end of the journal is ignored, unless the journal ends
+    /// before the end of its snapshot. Any other damaged record is an error.
+    #[allow(clippy::type_complexity)]
+    pub fn replay(
+        mut reader: impl Read,
+        key_codec: &impl Codec<K>,
+        value_codec: &impl Codec<V>,
+    ) -> Result<(Self, Vec<Handle<K, V>>), Error> {
+        let mut input = vec![];
+        reader.read_to_end(&mut input)?;
+        let mut reader = snapshot::Reader::new(&input);
+        read_header(&mut reader)?;
+
+        let map = LinkedHashMap::new();
+        let mut handles = HashMap::new();
+        let mut record = 0;
+        let mut snapshotted = false;
+        while !reader.is_empty() {
+            let Some(frame) = read_frame(&mut reader, record)? else {
+                break;
+            };
+            let (tag, fields) = read_record(frame, record)?;
+            let decode_key = |field| snapshot::decode(key_codec, field, record);
+            match (tag, &fields[..]) {
+                (INSERT, [key, value]) => {
+                    let key = decode_key(key)?;
+                    let value = snapshot::decode(value_codec, value, record)?;
+                    let handle = map.insert(key.clone(), value).handle;
+                    handles.insert(key, handle);
+                }
+                (REMOVE, [key]) => drop(handles.remove(&decode_key(key)?)),
+                (ORDER, keys) => {
+                    let positions = (keys.iter().enumerate())
+                        .map(|(i, key)| Ok((decode_key(key)?, i)))
+                        .collect::<Result<HashMap<_, _>, snapshot::Error>>()?;
+                    map.sort_by(|(a, _), (b, _)| positions.get(a).cmp(&positions.get(b)));
+                }
+                (SNAPSHOT, []) => snapshotted = true,
+                _ => unreachable!("Records are validated by read_record"),
+            }
+            record += 1;
+        }
+        if !snapshotted {
+            return Err(snapshot::Error::Truncated.into());
+        }
+
+        let mut ordered = Vec::with_capacity(handles.len());
+        map.for_each(|key, _| ordered.push(handles.remove(key).unwrap()));
+        Ok((map, ordered))
+    }
+}
+
+impl<K, V, W> Journal<K, V, W>
+where
+    K: Clone + Eq + Hash,
+    W: Write,
+{
+    /// Flushes the writer, or returns the error it previously returned.
+    pub fn flush(&self) -> io::Result<()> {
+        let Sink { writer, error, .. } = &mut self.state.borrow_mut().sink;
+        if let Some(error) = error {
+            return Err(io::Error::new(error.kind(), error.to_string()));
+        }
+        writer.flush()
+    }
+
+    /// The number of records written after the snapshot that starts the journal.
+    pub fn records(&self) -> usize {
+        self.state.borrow().sink.records
+    }
+
+    /// Continues the journal in `writer`, starting with a snapshot of the map, and returns the
+    /// previous writer. A previous write error is cleared.
+    ///
+    /// Replaying the new journal gives the same map as replaying the previous one. If `writer`
+    /// fails, it is returned with its error, and records are still written to the previous writer,
+    /// or not at all if it failed before.
+    pub fn compact(&self, writer: W) -> Result<W, (W, io::Error)> {
+        let mut state = self.state.borrow_mut();
+        let sink = Sink::start(writer, &state.map, &*state.key_codec, &*state.value_codec)?;
+        Ok(core::mem::replace(&mut state.sink, sink).writer)
+    }
+
+    /// Compacts the journal whenever more than `ratio` records per entry of the map were written
+    /// since its snapshot, into a writer returned by `writers`. The previous writer is dropped.
+    ///
+    /// If `writers` or the compaction fails, the journal carries on in its current writer, and is
+    /// compacted again after the next record. `writers` must not modify the map.
+    pub fn compact_automatically(
+        &self,
+        ratio: usize,
+        writers: impl FnMut() -> io::Result<W> + 'static,
+    ) {
+        self.state.borrow_mut().compaction = Some(Compaction {
+            ratio,
+            writers: Box::new(writers),
+        });
+    }
+}
+
+impl<K, V, W> State<K, V, W>
+where
+    K: Clone + Eq + Hash,
+    W: Write,
+{
+    fn record(&mut self, event: &Event<K, V>) {
+        match event {
+            Event::Inserted { key } | Event::Replaced { key } => {
+                let map = self.map.clone();
+                let map = map.borrow_map();
+                // A subscriber that ran before may have removed the entry already.
+                let Some(entry) = map.get(key) else {
+                    return;
+                };
+                self.sink
+                    .insert(key, &entry.value, &*self.key_codec, &*self.value_codec);
+            }
+            Event::Removed { key, .. } => {
+                let key_codec = &self.key_codec;
+                self.sink.write(REMOVE, |output| {
+                    snapshot::try_write_field(output, |output| key_codec.encode(key, output))
+                });
+            }
+            // Only replacing a value moves an entry, and the insert record already moves it.
+            Event::Moved { .. } => {}
+            Event::Reordered => {
+                let map = self.map.clone();
+                let key_codec = &self.key_codec;
+                self.sink.write(ORDER, |output| {
+                    output.extend_from_slice(&(map.len() as u64).to_le_bytes());
+                    let mut result = Ok(());
+                    map.for_each(|key, _| {
+                        if result.is_ok() {
+                            result = snapshot::try_write_field(output, |output| {
+                                key_codec.encode(key, output)
+                            });
+                        }
+                    });
+                    result
+                });
+            }
+        }
+        self.compact_if_needed();
+    }
+
+    fn compact_if_needed(&mut self) {
+        let Some(compaction) = &mut self.compaction else {
+            return;
+        };
+        if self.sink.records <= compaction.ratio.saturating_mul(self.map.len().max(1)) {
+            return;
+        }
+        let Ok(writer) = (compaction.writers)() else {
+            return;
+        };
+        let (key_codec, value_codec) = (&*self.key_codec, &*self.value_codec);
+        if let Ok(sink) = Sink::start(writer, &self.map, key_codec, value_codec) {
+            self.sink = sink;
+        }
+    }
+}
+
+impl<W> Sink<W>
+where
+    W: Write,
+{
+    /// Writes the header and a snapshot of `map` into `writer`, which is given back if it fails.
+    fn start<K, V>(
+        mut writer: W,
+        map: &LinkedHashMap<K, V>,
+        key_codec: &dyn Codec<K>,
+        value_codec: &dyn Codec<V>,
+    ) -> Result<Self, (W, io::Error)>
+    where
+        K: Clone + Eq + Hash,
+    {
+        let mut header = vec![];
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        snapshot::write_checksum(0, &mut header);
+        if let Err(error) = writer.write_all(&header) {
+            return Err((writer, error));
+        }
+
+        let mut sink = Sink {
+            writer,
+            records: 0,
+            error: None,
+        };
+        map.for_each(|key, value| sink.insert(key, value, key_codec, value_codec));
+        sink.write(SNAPSHOT, |_| Ok(()));
+        sink.records = 0;
+        match sink.error.take() {
+            Some(error) => Err((sink.writer, error)),
+            None => Ok(sink),
+        }
+    }
+
+    fn insert<K, V>(
+        &mut self,
+        key: &K,
+        value: &V,
+        key_codec: &dyn Codec<K>,
+        value_codec: &dyn Codec<V>,
+    ) {
+        self.write(INSERT, |output| {
+            snapshot::try_write_field(output, |output| key_codec.encode(key, output))?;
+            snapshot::try_write_field(output, |output| value_codec.encode(value, output))
+        });
+    }
+
+    fn write(&mut self, tag: u8, fields: impl FnOnce(&mut Vec<u8>) -> Result<(), TryFromIntError>) {
+        if self.error.is_some() {
+            return;
+        }
+        let mut record = vec![0; 8];
+        record.push(tag);
+        let len = fields(&mut record).and_then(|()| u32::try_from(record.len() - 8));
+        let Ok(len) = len else {
+            let error = io::Error::new(io::ErrorKind::InvalidInput, "The record exceeds 4 GiB");
+            self.error = Some(error);
+            return;
+        };
+        record[..4].copy_from_slice(&len.to_le_bytes());
+        let checksum = snapshot::crc32(&record[..4]);
+        record[4..8].copy_from_slice(&checksum.to_le_bytes());
+        snapshot::write_checksum(8, &mut record);
+        match self.writer.write_all(&record) {
+            Ok(()) => self.records += 1,
+            Err(error) => self.error = Some(error),
+        }
+    }
+}
+
+fn read_header(reader: &mut snapshot::Reader) -> Result<(), snapshot::Error> {
+    let start = reader.remaining();
+    if reader.take_array()? != MAGIC {
+        return Err(snapshot::Error::BadMagic);
+    }
+    let version = u16::from_le_bytes(reader.take_array()?);
+    reader.checksum(reader.since(start), None)?;
+    if version != VERSION {
+        return Err(snapshot::Error::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// Reads the frame of a record and returns the bytes it holds, once both its checksums match.
+///
+/// Returns `None` if the journal ends before the end of the frame.
+fn read_frame<'t>(
+    reader: &mut snapshot::Reader<'t>,
+    record: u64,
+) -> Result<Option<&'t [u8]>, snapshot::Error> {
+    if reader.remaining().len() < 8 {
+        return Ok(None);
+    }
+    let start = reader.remaining();
+    let len = u32::from_le_bytes(reader.take_array()?) as usize;
+    reader.checksum(reader.since(start), Some(record))?;
+    if reader.remaining().len() < len + 4 {
+        return Ok(None);
+    }
+    let frame = reader.take(len)?;
+    reader.checksum(frame, Some(record))?;
+    Ok(Some(frame))
+}
+
+/// Reads the tag and the fields of a record from its verified frame.
+fn read_record(frame: &[u8], record: u64) -> Result<(u8, Vec<&[u8]>), Error> {
+    let mut reader = snapshot::Reader::new(frame);
+    let [tag] = reader.take_array()?;
+    let count = match tag {
+        INSERT => 2,
+        REMOVE => 1,
+        SNAPSHOT => 0,
+        ORDER => u64::from_le_bytes(reader.take_array()?),
+        _ => return Err(Error::UnknownRecord { tag, record }),
+    };
+    let mut fields = vec![];
+    for _ in 0..count {
+        fields.push(reader.field()?);
+    }
+    if !reader.is_empty() {
+        return Err(snapshot::Error::TrailingBytes.into());
+    }
+    Ok((tag, fields))
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<snapshot::Error> for Error {
+    fn from(error: snapshot::Error) -> Self {
+        Error::Invalid(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "Cannot read the journal: {error}"),
+            Error::Invalid(error) => write!(f, "Invalid journal: {error}"),
+            Error::UnknownRecord { tag, record } => {
+                write!(f, "Invalid journal: unknown tag {tag} in record {record}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Invalid(error) => Some(error),
+            Error::UnknownRecord { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Write;
+
+    use super::Error;
+    use crate::hash_map::LinkedHashMap;
+    use crate::snapshot;
+    use crate::snapshot::Utf8;
+
+    type Map = LinkedHashMap<String, String>;
+
+    fn entries(map: &Map) -> Vec<(String, String)> {
+        map.iter().collect()
+    }
+
+    fn replay(journal: &[u8]) -> Result<Map, Error> {
+        let (map, handles) = Map::replay(journal, &Utf8, &Utf8)?;
+        assert_eq!(map.len(), handles.len());
+        // The handles own the entries.
+        std::mem::forget(handles);
+        Ok(map)
+    }
+
+    #[test]
+    fn replay_changes() {
+        let map = Map::new();
+        let a = map.insert("a".into(), "1".into()).handle;
+        let journal = map.journal(vec![], Utf8, Utf8).unwrap();
+        let b = map.insert("b".into(), "2".into()).handle;
+        let _c = map.insert("c".into(), "3".into()).handle;
+        let _a = map.insert("a".into(), "4".into()).handle;
+        drop((a, b));
+        let _d = map.insert("d".into(), "5".into()).handle;
+        map.sort_by(|(a, _), (b, _)| b.cmp(a));
+        map.sort_keys();
+        map.retain(|k, _| k != "c");
+        // Inserting 2, 3, 4 and 5, removing "b" and "c", and sorting twice.
+        assert_eq!(8, journal.records());
+
+        let written = journal.compact(vec![]).unwrap();
+        assert_eq!(entries(&map), entries(&replay(&written).unwrap()));
+        assert_eq!(0, journal.records());
+        let compacted = journal.compact(vec![]).unwrap();
+        assert!(compacted.len() < written.len());
+        assert_eq!(entries(&map), entries(&replay(&compacted).unwrap()));
+
+        // The map is no longer journaled.
+        drop(journal);
+        let _e = map.insert("e".into(), "6".into()).handle;
+    }
+
+    #[test]
+    fn crash() {
+        let map = Map::new();
+        let journal = map.journal(vec![], Utf8, Utf8).unwrap();
+        let _a = map.insert("a".into(), "1".into()).handle;
+        let _b = map.insert("b".into(), "2".into()).handle;
+        let written = journal.compact(vec![]).unwrap();
+
+        // The last record was not fully written.
+        let torn = replay(&written[..written.len() - 3]).unwrap();
+        assert_eq!(vec![("a".to_string(), "1".to_string())], entries(&torn));
+
+        // The journal ends inside its snapshot.
+        let compacted = journal.compact(vec![]).unwrap();
+        assert!(matches!(
+            replay(&compacted[..compacted.len() - 3]),
+            Err(Error::Invalid(snapshot::Error::Truncated))
+        ));
+
+        let mut corrupt = written.clone();
+        let len = corrupt.len();
+        corrupt[len - 5] ^= 1;
+        assert!(matches!(
+            replay(&corrupt),
+            Err(Error::Invalid(snapshot::Error::ChecksumMismatch {
+                record: Some(2)
+            }))
+        ));
+
+        // The length of the record of "a" is damaged: the records after it cannot be found.
+        let mut corrupt = written.clone();
+        let snapshot_len = u32::from_le_bytes(written[10..14].try_into().unwrap()) as usize;
+        corrupt[10 + 8 + snapshot_len + 4] ^= 1;
+        assert!(matches!(
+            replay(&corrupt),
+            Err(Error::Invalid(snapshot::Error::ChecksumMismatch {
+                record: Some(1)
+            }))
+        ));
+
+        let mut unknown = written.clone();
+        unknown.extend_from_slice(&1u32.to_le_bytes());
+        unknown.extend_from_slice(&snapshot::crc32(&1u32.to_le_bytes()).to_le_bytes());
+        unknown.push(9);
+        unknown.extend_from_slice(&snapshot::crc32(&[9]).to_le_bytes());
+        assert!(matches!(
+            replay(&unknown),
+            Err(Error::UnknownRecord { tag: 9, record: 3 })
+        ));
+        assert!(matches!(
+            replay(b"LNKS"),
+            Err(Error::Invalid(snapshot::Error::BadMagic))
+        ));
+    }
+
+    #[test]
+    fn compact_automatically() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let map = Map::new();
+        let journal = map.journal(vec![], Utf8, Utf8).unwrap();
+        let compactions = Rc::new(Cell::new(0));
+        journal.compact_automatically(2, {
+            let compactions = compactions.clone();
+            move || {
+                compactions.set(compactions.get() + 1);
+                Ok(vec![])
+            }
+        });
+        let _a = map.insert("a".into(), "0".into()).handle;
+        let _b = map.insert("b".into(), "0".into()).handle;
+        for i in 1..10 {
+            let _a = map.insert("a".into(), i.to_string());
+            assert!(journal.records() <= 4);
+        }
+        assert_eq!(2, compactions.get());
+        let written = journal.compact(vec![]).unwrap();
+        assert_eq!(entries(&map), entries(&replay(&written).unwrap()));
+    }
+
+    #[derive(Debug)]
+    struct Failing(bool);
+
+    impl Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.0 {
+                true => Err(io::Error::other("disk full")),
+                false => Ok(buf.len()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_compaction() {
+        let map = Map::new();
+        let journal = map.journal(Failing(false), Utf8, Utf8).unwrap();
+        let _a = map.insert("a".into(), "1".into()).handle;
+        assert!(journal.compact(Failing(true)).is_err());
+        let _b = map.insert("b".into(), "2".into()).handle;
+        assert_eq!(2, journal.records());
+        journal.flush().unwrap();
+    }
+
+    #[test]
+    fn write_error() {
+        let map = Map::new();
+        let journal = map.journal(Failing(false), Utf8, Utf8).unwrap();
+        journal.state.borrow_mut().sink.writer.0 = true;
+        let _a = map.insert("a".into(), "1".into()).handle;
+        let _b = map.insert("b".into(), "2".into()).handle;
+        assert_eq!("disk full", journal.flush().unwrap_err().to_string());
+        assert_eq!(0, journal.records());
+
+        let (writer, error) = journal.compact(Failing(true)).unwrap_err();
+        assert!(writer.0);
+        assert_eq!("disk full", error.to_string());
+        assert!(journal.flush().is_err());
+
+        assert!(journal.compact(Failing(false)).unwrap().0);
+        journal.flush().unwrap();
+        assert!(map.journal(Failing(true), Utf8, Utf8).is_err());
+    }
+}
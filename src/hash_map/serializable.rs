@@ -1,19 +1,42 @@
-use std::hash::Hash;
+use alloc::vec::Vec;
+use core::hash::Hash;
 
-use super::collectible::IsCollectibleItem;
+use serde::ser::SerializeSeq;
+
+use super::Handle;
 use super::LinkedHashMap;
 use super::LinkedHashSet;
+use crate::collectible::Collectible;
+use crate::list::serializable::SeqVisitor;
+
+/// Deserializes a [LinkedHashMap] of plain keys and values, along with the [Handle]s that own its
+/// entries, in order. Dropping a handle removes its entry.
+pub struct MapWithHandles<K, V>(pub LinkedHashMap<K, V>, pub Vec<Handle<K, V>>)
+where
+    K: Clone + Eq + Hash;
 
+/// Deserializes a [LinkedHashSet] of plain keys, along with the [Handle]s that own its elements.
+/// See [MapWithHandles].
+pub struct SetWithHandles<K>(pub LinkedHashSet<K>, pub Vec<Handle<K, ()>>)
+where
+    K: Clone + Eq + Hash;
+
+/// Entries are borrowed from the map while it is serialized.
 impl<K, V> serde::Serialize for LinkedHashMap<K, V>
 where
     K: serde::Serialize + Clone + Eq + Hash,
-    V: serde::Serialize + Clone,
+    V: serde::Serialize,
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.collect_seq(self.iter())
+        let map = self.borrow_map();
+        let mut seq = serializer.serialize_seq(Some(map.len()))?;
+        for key in self.keys.iter() {
+            seq.serialize_element(&(&*key, &map[&*key].value))?;
+        }
+        seq.end()
     }
 }
 
@@ -25,99 +48,227 @@ where
     where
         S: serde::Serializer,
     {
-        serializer.collect_seq(self.iter())
+        let keys = &self.linked_hash_map.keys;
+        let len = self.linked_hash_map.borrow_map().len();
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for key in keys.iter() {
+            seq.serialize_element(&*key)?;
+        }
+        seq.end()
     }
 }
 
 impl<'de, K, V> serde::Deserialize<'de> for LinkedHashMap<K, V>
 where
-    K: serde::Deserialize<'de> + Clone + Eq + Hash,
-    V: serde::Deserialize<'de> + IsCollectibleItem<Key = K, Value = V>,
+    K: serde::Deserialize<'de> + Clone + Eq + Hash + 'static,
+    V: serde::Deserialize<'de> + Collectible + 'static,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let data = Vec::<(K, V)>::deserialize(deserializer)?;
-        Ok(data.into_iter().collect())
+        deserializer.deserialize_seq(SeqVisitor::new(
+            LinkedHashMap::with_capacity,
+            |map: &mut Self, entry| map.extend([entry]),
+        ))
     }
 }
 
 impl<'de, K> serde::Deserialize<'de> for LinkedHashSet<K>
 where
-    K: serde::Deserialize<'de> + Clone + Eq + Hash + IsCollectibleItem<Key = K, Value = ()>,
+    K: serde::Deserialize<'de> + Collectible + Clone + Eq + Hash + 'static,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let data = Vec::<K>::deserialize(deserializer)?;
-        Ok(data.into_iter().collect())
+        deserializer.deserialize_seq(SeqVisitor::new(
+            LinkedHashSet::with_capacity,
+            |set: &mut Self, key| set.extend([key]),
+        ))
     }
 }
 
-mod value {
-    use std::hash::Hash;
+impl<'de, K, V> serde::Deserialize<'de> for MapWithHandles<K, V>
+where
+    K: serde::Deserialize<'de> + Clone + Eq + Hash,
+    V: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor::new(
+            |capacity| {
+                Self(
+                    LinkedHashMap::with_capacity(capacity),
+                    Vec::with_capacity(capacity),
+                )
+            },
+            |Self(map, handles): &mut Self, (key, value)| {
+                handles.push(map.insert(key, value).handle)
+            },
+        ))
+    }
+}
 
-    use super::super::collectible::value::CollectibleValue;
+impl<'de, K> serde::Deserialize<'de> for SetWithHandles<K>
+where
+    K: serde::Deserialize<'de> + Clone + Eq + Hash,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor::new(
+            |capacity| {
+                Self(
+                    LinkedHashSet::with_capacity(capacity),
+                    Vec::with_capacity(capacity),
+                )
+            },
+            |Self(set, handles): &mut Self, key| handles.push(set.insert(key).handle),
+        ))
+    }
+}
+
+/// Serializes a [LinkedHashMap] as a map instead of a sequence of pairs, preserving the order of
+/// the entries.
+///
+/// Use it with `#[serde(with = "linked::hash_map::serializable::as_map")]`. Maps of plain values
+/// use [as_map::with_handles] instead.
+pub mod as_map {
+    use core::fmt;
+    use core::hash::Hash;
+    use core::marker::PhantomData;
+
+    use serde::de::MapAccess;
+    use serde::de::Visitor;
+    use serde::ser::SerializeMap;
+    use serde::Deserializer;
+    use serde::Serializer;
 
-    impl<K, V> serde::Serialize for CollectibleValue<K, V>
+    use super::LinkedHashMap;
+    use crate::collectible::Collectible;
+    use crate::list::serializable::cautious;
+
+    pub fn serialize<K, V, S>(map: &LinkedHashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
     where
         K: serde::Serialize + Clone + Eq + Hash,
-        V: serde::Serialize + Clone,
+        V: serde::Serialize,
+        S: Serializer,
     {
-        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-        where
-            S: serde::Serializer,
-        {
-            let value: &V = &*self;
-            value.serialize(serializer)
+        let entries = map.borrow_map();
+        let mut output = serializer.serialize_map(Some(entries.len()))?;
+        for key in map.keys.iter() {
+            output.serialize_entry(&*key, &entries[&*key].value)?;
         }
+        output.end()
     }
 
-    impl<'de, K, V> serde::Deserialize<'de> for CollectibleValue<K, V>
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<LinkedHashMap<K, V>, D::Error>
     where
-        K: serde::Deserialize<'de> + Clone + Eq + Hash,
-        V: serde::Deserialize<'de> + Clone,
+        K: serde::Deserialize<'de> + Clone + Eq + Hash + 'static,
+        V: serde::Deserialize<'de> + Collectible + 'static,
+        D: Deserializer<'de>,
     {
-        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        deserializer.deserialize_map(MapVisitor::new(
+            LinkedHashMap::with_capacity,
+            |map: &mut LinkedHashMap<K, V>, entry| map.extend([entry]),
+        ))
+    }
+
+    /// Like [as_map](self), for a [MapWithHandles](super::MapWithHandles) of plain keys and
+    /// values.
+    ///
+    /// Use it with `#[serde(with = "linked::hash_map::serializable::as_map::with_handles")]`.
+    pub mod with_handles {
+        use alloc::vec::Vec;
+        use core::hash::Hash;
+
+        use serde::Deserializer;
+        use serde::Serializer;
+
+        use super::super::MapWithHandles;
+        use super::LinkedHashMap;
+        use super::MapVisitor;
+
+        pub fn serialize<K, V, S>(
+            map: &MapWithHandles<K, V>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
         where
-            D: serde::Deserializer<'de>,
+            K: serde::Serialize + Clone + Eq + Hash,
+            V: serde::Serialize,
+            S: Serializer,
         {
-            let value = V::deserialize(deserializer)?;
-            Ok(CollectibleValue::from(value))
+            super::serialize(&map.0, serializer)
         }
-    }
-}
 
-mod key {
-    use std::hash::Hash;
+        pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<MapWithHandles<K, V>, D::Error>
+        where
+            K: serde::Deserialize<'de> + Clone + Eq + Hash,
+            V: serde::Deserialize<'de>,
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_map(MapVisitor::new(
+                |capacity| {
+                    MapWithHandles(
+                        LinkedHashMap::with_capacity(capacity),
+                        Vec::with_capacity(capacity),
+                    )
+                },
+                |MapWithHandles(map, handles): &mut MapWithHandles<K, V>, (key, value)| {
+                    handles.push(map.insert(key, value).handle)
+                },
+            ))
+        }
+    }
 
-    use super::super::collectible::key::CollectibleKey;
+    /// Deserializes a map entry by entry, like [SeqVisitor](crate::list::serializable::SeqVisitor)
+    /// does for sequences.
+    struct MapVisitor<C, K, V, I, P> {
+        init: I,
+        push: P,
+        _phantom: PhantomData<(C, K, V)>,
+    }
 
-    impl<K> serde::Serialize for CollectibleKey<K>
+    impl<C, K, V, I, P> MapVisitor<C, K, V, I, P>
     where
-        K: serde::Serialize + Clone + Eq + Hash,
+        I: FnOnce(usize) -> C,
+        P: FnMut(&mut C, (K, V)),
     {
-        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-        where
-            S: serde::Serializer,
-        {
-            let key: &K = &*self;
-            key.serialize(serializer)
+        fn new(init: I, push: P) -> Self {
+            Self {
+                init,
+                push,
+                _phantom: PhantomData,
+            }
         }
     }
 
-    impl<'de, K> serde::Deserialize<'de> for CollectibleKey<K>
+    impl<'de, C, K, V, I, P> Visitor<'de> for MapVisitor<C, K, V, I, P>
     where
-        K: serde::Deserialize<'de> + Clone + Eq + Hash,
+        K: serde::Deserialize<'de>,
+        V: serde::Deserialize<'de>,
+        I: FnOnce(usize) -> C,
+        P: FnMut(&mut C, (K, V)),
     {
-        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        type Value = C;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map")
+        }
+
+        fn visit_map<A>(mut self, mut access: A) -> Result<Self::Value, A::Error>
         where
-            D: serde::Deserializer<'de>,
+            A: MapAccess<'de>,
         {
-            let key = K::deserialize(deserializer)?;
-            Ok(CollectibleKey::from(key))
+            let mut map = (self.init)(cautious::<(K, V)>(access.size_hint()));
+            while let Some(entry) = access.next_entry()? {
+                (self.push)(&mut map, entry);
+            }
+            Ok(map)
         }
     }
 }
@@ -0,0 +1,140 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use super::LinkedHashMap;
+use crate::list;
+use crate::list::BoxNodeFactory;
+use crate::list::LinkedList;
+
+/// A change made to a [LinkedHashMap].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<K, V> {
+    /// A new entry was added at the back of the map.
+    Inserted { key: K },
+
+    /// The value of an existing entry was replaced.
+    Replaced { key: K },
+
+    /// An existing entry changed position in the iteration order.
+    Moved { key: K },
+
+    /// The entries were reordered in place, by a sort.
+    Reordered,
+
+    /// An entry was removed, typically because its last [Handle](super::Handle) was dropped.
+    Removed { key: K, value: V },
+}
+
+type Callback<K, V> = Rc<dyn Fn(&Event<K, V>)>;
+
+pub(super) type Subscribers<K, V> = LinkedList<Callback<K, V>>;
+
+/// Keeps a callback registered with [LinkedHashMap::subscribe].
+///
+/// The callback is unregistered when the subscription is dropped.
+#[must_use]
+pub struct Subscription<K, V>(#[allow(unused)] list::Handle<BoxNodeFactory<Callback<K, V>>>);
+
+impl<K, V> LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    /// Registers a callback that is invoked after every change to the map.
+    ///
+    /// Callbacks run once the map is no longer borrowed, so they may read or modify the map.
+    pub fn subscribe(&self, callback: impl Fn(&Event<K, V>) + 'static) -> Subscription<K, V> {
+        Subscription(self.subscribers.push_back(Rc::new(callback)))
+    }
+
+    pub(super) fn has_subscribers(&self) -> bool {
+        self.subscribers.current().is_some()
+    }
+
+    /// Notifies the removal of an entry, then gives it back.
+    pub(super) fn notify_removed(&self, key: K, value: V) -> (K, V) {
+        let event = Event::Removed { key, value };
+        if self.has_subscribers() {
+            self.notify(&event);
+        }
+        let Event::Removed { key, value } = event else {
+            unreachable!()
+        };
+        (key, value)
+    }
+
+    pub(super) fn notify(&self, event: &Event<K, V>) {
+        // Callbacks are collected first: a callback may drop its own subscription.
+        let callbacks = self.subscribers.values().collect::<Vec<_>>();
+        for callback in callbacks {
+            callback(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    use super::Event;
+    use crate::hash_map::LinkedHashMap;
+
+    fn record(
+        map: &LinkedHashMap<String, i32>,
+    ) -> (Rc<RefCell<Vec<String>>>, super::Subscription<String, i32>) {
+        let events = Rc::new(RefCell::new(vec![]));
+        let subscription = map.subscribe({
+            let events = events.clone();
+            move |event| events.borrow_mut().push(format!("{event:?}"))
+        });
+        (events, subscription)
+    }
+
+    #[test]
+    fn events() {
+        let map = LinkedHashMap::<String, i32>::new();
+        let (events, subscription) = record(&map);
+
+        let a = map.insert("a".into(), 1).handle;
+        let _b = map.insert("b".into(), 2).handle;
+        let a2 = map.insert("a".into(), 3).handle;
+        drop(a);
+        assert_eq!(
+            vec![
+                r#"Inserted { key: "a" }"#,
+                r#"Inserted { key: "b" }"#,
+                r#"Replaced { key: "a" }"#,
+                r#"Moved { key: "a" }"#,
+            ],
+            *events.borrow()
+        );
+
+        drop(a2);
+        assert_eq!(
+            Some(&r#"Removed { key: "a", value: 3 }"#.to_string()),
+            events.borrow().last()
+        );
+
+        drop(subscription);
+        let _c = map.insert("c".into(), 4).handle;
+        assert_eq!(5, events.borrow().len());
+    }
+
+    #[test]
+    fn reentrant() {
+        let map = LinkedHashMap::<String, i32>::new();
+        let _subscription = map.subscribe({
+            let map = map.clone();
+            move |event| {
+                if let Event::Removed { key, .. } = event {
+                    assert_eq!(None, map.get(key));
+                    assert!(!map.keys().any(|k| k == *key));
+                }
+            }
+        });
+        let a = map.insert("a".into(), 1).handle;
+        let _b = map.insert("b".into(), 2).handle;
+        drop(a);
+    }
+}
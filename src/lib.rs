@@ -1,4 +1,19 @@
 #![deny(warnings)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "derive")]
+pub use linked_derive::Collectible;
+
+pub mod collectible;
+#[cfg(feature = "std")]
+pub mod concurrent;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
 pub mod hash_map;
 pub mod list;
+#[cfg(all(feature = "serde", feature = "std"))]
+pub mod session;
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod sync;
@@ -1,14 +1,24 @@
-use std::cell::Cell;
-use std::rc::Rc;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::cmp::Ordering;
 
 use scopeguard::guard;
 
+#[cfg(feature = "std")]
+pub use self::arena_node_factory::ArenaNodeFactory;
 pub use self::handle::Handle;
 use self::implem::LinkedListImpl;
-use self::node_factory::NodeFactory;
+pub use self::node::Node;
+pub use self::node_factory::BoxNodeFactory;
+pub use self::node_factory::NodeFactory;
+pub use self::node_factory::RawRef;
 pub use self::node_factory::RcNodeFactory;
-use self::node_ref::NodeRef;
+pub use self::node_ref::NodeRef;
 
+#[cfg(feature = "std")]
+mod arena_node_factory;
 mod handle;
 mod implem;
 mod iterator;
@@ -16,14 +26,18 @@ mod node;
 mod node_factory;
 mod node_ref;
 
-pub mod collectible;
+mod collectible;
+pub mod conformance;
+#[cfg(feature = "serde")]
 pub mod serializable;
 
-pub struct LinkedList<V, F = RcNodeFactory<V>>
+pub struct LinkedList<V, F = BoxNodeFactory<V>>
 where
     F: NodeFactory<Value = V>,
 {
     list: Rc<LinkedListImpl<F>>,
+    /// How many nodes after the first node of the list this view starts, see [LinkedList::next].
+    rotation: isize,
 }
 
 impl<V, F> LinkedList<V, F>
@@ -33,6 +47,7 @@ where
     pub fn new() -> Self {
         Self {
             list: Rc::new(LinkedListImpl::default()),
+            rotation: 0,
         }
     }
 }
@@ -45,40 +60,156 @@ where
         self.list.push_back(value)
     }
 
-    pub fn prev(&self) -> Self {
-        LinkedList {
-            list: self.list.prev(),
+    /// Like [LinkedList::push_back], but `on_remove` receives the value once the handle is dropped
+    /// and the node is unlinked.
+    pub fn push_back_with_on_remove(
+        &self,
+        value: V,
+        on_remove: impl FnOnce(V) + 'static,
+    ) -> Handle<F> {
+        let mut handle = self.list.push_back(value);
+        handle.on_remove = Some(Box::new(Box::new(on_remove)));
+        handle
+    }
+
+    /// Moves all the nodes of `other` to the back of this list in O(1), leaving `other` empty.
+    ///
+    /// Handles follow their nodes.
+    ///
+    /// Panics if either list is a rotated view of a list, as returned by [LinkedList::prev] or
+    /// [LinkedList::next].
+    pub fn append(&self, other: &Self) {
+        assert!(
+            self.rotation == 0 && other.rotation == 0,
+            "Rotated views of a list cannot be appended"
+        );
+        self.list.append(&other.list)
+    }
+
+    /// Moves the node of `handle` and all the nodes after it into a new list.
+    ///
+    /// Handles follow their nodes. Checking that `handle` belongs to this list takes time
+    /// proportional to the number of nodes that are moved.
+    ///
+    /// Panics if `handle` does not belong to this list, or if this list is a rotated view.
+    pub fn split_off_at(&self, handle: &Handle<F>) -> Self {
+        assert!(
+            self.rotation == 0,
+            "Rotated views of a list cannot be split"
+        );
+        let node_ptr = F::downgrade(&handle.node);
+        Self {
+            list: self.list.split_off(&handle.node, node_ptr),
+            rotation: 0,
         }
     }
 
+    /// Moves the nodes before the node of `handle` into a new list.
+    ///
+    /// Handles follow their nodes. Checking that `handle` belongs to this list takes time
+    /// proportional to the number of nodes that stay in this list.
+    ///
+    /// Panics if `handle` does not belong to this list, or if this list is a rotated view.
+    pub fn split_before(&self, handle: &Handle<F>) -> Self {
+        let result = self.split_off_at(handle);
+        self.list.swap(&result.list);
+        result
+    }
+
+    /// Unlinks the values for which `keep` returns false.
+    ///
+    /// Values are owned by their [Handle], so the handles of unlinked values are detached: they
+    /// keep their value but no longer belong to any list.
+    pub fn retain(&self, keep: impl FnMut(&V) -> bool) {
+        self.list.retain(keep)
+    }
+
+    /// Unlinks all the values, detaching their handles. See [LinkedList::retain].
+    pub fn clear(&self) {
+        self.list.retain(|_| false)
+    }
+
+    /// Unlinks all the values, detaching their handles, and returns copies of the values.
+    ///
+    /// Values are owned by their [Handle], so they can only be cloned out of the list.
+    pub fn drain(&self) -> impl Iterator<Item = V>
+    where
+        V: Clone,
+    {
+        let values = self.values().collect::<Vec<_>>();
+        self.clear();
+        values.into_iter()
+    }
+
+    /// Sorts the list in place by relinking its nodes. Handles stay valid.
+    ///
+    /// The sort is stable.
+    pub fn sort_by(&self, compare: impl FnMut(&V, &V) -> Ordering) {
+        self.list.sort_by(compare)
+    }
+
+    /// Sorts the list in place by relinking its nodes. See [LinkedList::sort_by].
+    pub fn sort_by_key<K: Ord>(&self, mut key: impl FnMut(&V) -> K) {
+        self.list.sort_by(|a, b| key(a).cmp(&key(b)))
+    }
+
+    /// Reverses the list in place by relinking its nodes. Handles stay valid.
+    pub fn reverse(&self) {
+        self.list.reverse()
+    }
+
+    /// A view of the same list that starts one node earlier.
+    ///
+    /// Views are resolved from the first node of the list each time they are used, in time
+    /// proportional to their rotation: they follow the list as its nodes are removed.
+    pub fn prev(&self) -> Self {
+        self.rotate(-1)
+    }
+
+    /// A view of the same list that starts one node later. See [LinkedList::prev].
     pub fn next(&self) -> Self {
-        LinkedList {
-            list: self.list.next(),
+        self.rotate(1)
+    }
+
+    fn rotate(&self, by: isize) -> Self {
+        Self {
+            list: self.list.clone(),
+            rotation: self.rotation + by,
         }
     }
 
     pub fn iter(&self) -> impl Iterator<Item = NodeRef<F>> {
-        self.list.iter()
+        self.list.iter(self.rotation)
     }
 
     pub fn values(&self) -> impl Iterator<Item = V>
     where
         V: Clone,
     {
-        self.list.iter().map(|v| v.clone())
+        self.iter().map(|v| v.clone())
     }
 
     pub fn current(&self) -> Option<NodeRef<F>> {
-        self.list.current()
+        self.list.current(self.rotation)
+    }
+
+    pub fn last(&self) -> Option<NodeRef<F>> {
+        self.list.last(self.rotation)
+    }
+
+    /// Iterates over the nodes that follow `handle`, which must belong to this list.
+    #[cfg(any(feature = "std", feature = "hashbrown"))]
+    pub(crate) fn iter_after(&self, handle: &Handle<F>) -> impl Iterator<Item = NodeRef<F>> {
+        self.list.iter_after(handle)
     }
 }
 
-impl<V, F> std::fmt::Debug for LinkedList<V, F>
+impl<V, F> core::fmt::Debug for LinkedList<V, F>
 where
-    V: std::fmt::Debug,
+    V: core::fmt::Debug,
     F: NodeFactory<Value = V>,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_list().entries(self.iter()).finish()
     }
 }
@@ -99,6 +230,7 @@ where
     fn clone(&self) -> Self {
         Self {
             list: Rc::clone(&self.list),
+            rotation: self.rotation,
         }
     }
 }
@@ -317,6 +449,256 @@ mod tests {
         assert_eq!(Vec::<String>::default(), to_vec());
     }
 
+    #[test]
+    fn on_remove() {
+        let removed = Rc::new(Cell::new(None));
+        let list = LinkedList::<String>::new();
+        let a = list.push_back_with_on_remove("a".into(), {
+            let removed = removed.clone();
+            move |v| removed.set(Some(v))
+        });
+        let _b = list.push_back("b".into());
+        assert_eq!(None, removed.take());
+
+        drop(a);
+        assert_eq!(Some("a".to_string()), removed.take());
+        assert_eq!(vec!["b"], list.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn handle_size() {
+        // Handles without a removal callback stay two pointers wide.
+        let size = core::mem::size_of::<Handle<BoxNodeFactory<String>>>();
+        assert_eq!(2 * core::mem::size_of::<usize>(), size);
+    }
+
+    #[test]
+    fn retain() {
+        let list = LinkedList::<i32>::new();
+        let handles = (1..=6).map(|i| list.push_back(i)).collect::<Vec<_>>();
+        list.retain(|v| v % 2 == 0);
+        assert_eq!(vec![2, 4, 6], list.values().collect::<Vec<_>>());
+
+        // Detached handles keep their value and don't affect the list.
+        assert_eq!(3, *handles[2].value());
+        let mut handles = handles.into_iter();
+        drop(handles.by_ref().take(3).collect::<Vec<_>>());
+        assert_eq!(vec![4, 6], list.values().collect::<Vec<_>>());
+        drop(handles);
+        assert_eq!(Vec::<i32>::new(), list.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn clear() {
+        let list = LinkedList::<String>::new();
+        let a = list.push_back("a".into());
+        let b = list.push_back("b".into());
+        list.clear();
+        assert_eq!(Vec::<String>::new(), list.values().collect::<Vec<_>>());
+
+        let c = list.push_back("c".into());
+        drop(a);
+        drop(b);
+        assert_eq!(vec!["c"], list.values().collect::<Vec<_>>());
+        drop(c);
+        assert_eq!(Vec::<String>::new(), list.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drain() {
+        let list = LinkedList::<String>::new();
+        let _a = list.push_back("a".into());
+        let _b = list.push_back("b".into());
+        assert_eq!(vec!["a", "b"], list.drain().collect::<Vec<_>>());
+        assert_eq!(Vec::<String>::new(), list.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sort() {
+        let list = LinkedList::<(i32, &str)>::new();
+        let a = list.push_back((2, "a"));
+        let b = list.push_back((1, "b"));
+        let c = list.push_back((2, "c"));
+        let d = list.push_back((0, "d"));
+
+        list.sort_by_key(|(k, _)| *k);
+        let names = || list.iter().map(|v| v.1).collect::<Vec<_>>();
+        assert_eq!(vec!["d", "b", "a", "c"], names());
+
+        list.reverse();
+        assert_eq!(vec!["c", "a", "b", "d"], names());
+
+        list.sort_by(|x, y| x.1.cmp(y.1));
+        assert_eq!(vec!["a", "b", "c", "d"], names());
+
+        drop(a);
+        assert_eq!(vec!["b", "c", "d"], names());
+        drop(d);
+        assert_eq!(vec!["b", "c"], names());
+        drop(b);
+        drop(c);
+        assert_eq!(Vec::<&str>::new(), names());
+    }
+
+    #[test]
+    fn append() {
+        let list1 = LinkedList::<String>::new();
+        let list2 = LinkedList::<String>::new();
+        let a = list1.push_back("a".into());
+        let b = list1.push_back("b".into());
+        let c = list2.push_back("c".into());
+        let d = list2.push_back("d".into());
+
+        list1.append(&list2);
+        assert_eq!(vec!["a", "b", "c", "d"], list1.values().collect::<Vec<_>>());
+        assert_eq!(Vec::<String>::new(), list2.values().collect::<Vec<_>>());
+
+        let e = list2.push_back("e".into());
+        assert_eq!(vec!["e"], list2.values().collect::<Vec<_>>());
+
+        drop(c);
+        drop(a);
+        assert_eq!(vec!["b", "d"], list1.values().collect::<Vec<_>>());
+
+        let empty = LinkedList::<String>::new();
+        empty.append(&list1);
+        assert_eq!(vec!["b", "d"], empty.values().collect::<Vec<_>>());
+        drop(b);
+        drop(d);
+        assert_eq!(Vec::<String>::new(), empty.values().collect::<Vec<_>>());
+        assert_eq!(vec!["e"], list2.values().collect::<Vec<_>>());
+        drop(e);
+    }
+
+    #[test]
+    fn split() {
+        let list = LinkedList::<String>::new();
+        let a = list.push_back("a".into());
+        let b = list.push_back("b".into());
+        let c = list.push_back("c".into());
+        let d = list.push_back("d".into());
+
+        let back = list.split_off_at(&c);
+        assert_eq!(vec!["a", "b"], list.values().collect::<Vec<_>>());
+        assert_eq!(vec!["c", "d"], back.values().collect::<Vec<_>>());
+
+        // Handles follow their nodes.
+        drop(c);
+        assert_eq!(vec!["d"], back.values().collect::<Vec<_>>());
+        drop(a);
+        assert_eq!(vec!["b"], list.values().collect::<Vec<_>>());
+
+        let front = back.split_before(&d);
+        assert_eq!(Vec::<String>::new(), front.values().collect::<Vec<_>>());
+        assert_eq!(vec!["d"], back.values().collect::<Vec<_>>());
+
+        list.append(&back);
+        let front = list.split_before(&d);
+        assert_eq!(vec!["b"], front.values().collect::<Vec<_>>());
+        assert_eq!(vec!["d"], list.values().collect::<Vec<_>>());
+
+        let all = list.split_off_at(&d);
+        assert_eq!(Vec::<String>::new(), list.values().collect::<Vec<_>>());
+        assert_eq!(vec!["d"], all.values().collect::<Vec<_>>());
+        drop(d);
+        assert_eq!(Vec::<String>::new(), all.values().collect::<Vec<_>>());
+        drop(b);
+        assert_eq!(Vec::<String>::new(), front.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "The handle does not belong to this list")]
+    fn split_other_list() {
+        let list1 = LinkedList::<String>::new();
+        let list2 = LinkedList::<String>::new();
+        let _a = list1.push_back("a".into());
+        let b = list2.push_back("b".into());
+        let _ = list1.split_off_at(&b);
+    }
+
+    #[test]
+    #[should_panic(expected = "Rotated views of a list cannot be appended")]
+    fn append_view() {
+        let list = LinkedList::<String>::new();
+        let _a = list.push_back("a".into());
+        let _b = list.push_back("b".into());
+        list.append(&list.next());
+    }
+
+    #[test]
+    #[should_panic(expected = "Rotated views of a list cannot be split")]
+    fn split_view() {
+        let list = LinkedList::<String>::new();
+        let _a = list.push_back("a".into());
+        let b = list.push_back("b".into());
+        let _ = list.prev().split_off_at(&b);
+    }
+
+    #[test]
+    fn move_to_list() {
+        let pending = LinkedList::<String>::new();
+        let running = LinkedList::<String>::new();
+        let a = pending.push_back("a".into());
+        let b = pending.push_back("b".into());
+        let c = pending.push_back("c".into());
+
+        a.move_to_list(&running);
+        c.move_to_list(&running);
+        assert_eq!(vec!["b"], pending.values().collect::<Vec<_>>());
+        assert_eq!(vec!["a", "c"], running.values().collect::<Vec<_>>());
+
+        b.move_to_list(&running);
+        a.move_to_list(&running);
+        assert_eq!(Vec::<String>::new(), pending.values().collect::<Vec<_>>());
+        assert_eq!(vec!["c", "b", "a"], running.values().collect::<Vec<_>>());
+
+        c.move_to_list(&pending);
+        drop(b);
+        assert_eq!(vec!["c"], pending.values().collect::<Vec<_>>());
+        assert_eq!(vec!["a"], running.values().collect::<Vec<_>>());
+        drop(c);
+        drop(a);
+        assert_eq!(Vec::<String>::new(), pending.values().collect::<Vec<_>>());
+        assert_eq!(Vec::<String>::new(), running.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rc_node_factory() {
+        let list = LinkedList::<String, RcNodeFactory<String>>::new();
+        let a = list.push_back("a".into());
+        let b = list.push_back_with_on_remove("b".into(), |v| assert_eq!("b", v));
+        let c = list.push_back("c".into());
+        assert_eq!(vec!["a", "b", "c"], list.values().collect::<Vec<_>>());
+        drop(b);
+        assert_eq!(vec!["a", "c"], list.values().collect::<Vec<_>>());
+        drop(a);
+        drop(c);
+        assert_eq!(Vec::<String>::new(), list.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize() {
+        let list = LinkedList::<String, RcNodeFactory<String>>::new();
+        let _a = list.push_back("a".into());
+        let _b = list.push_back("b".into());
+        let _c = list.push_back("c".into());
+        assert_eq!(r#"["a","b","c"]"#, serde_json::to_string(&list).unwrap());
+        assert_eq!(
+            r#"["c","a","b"]"#,
+            serde_json::to_string(&list.prev()).unwrap()
+        );
+        assert_eq!(
+            r#"["b","c","a"]"#,
+            serde_json::to_string(&list.next()).unwrap()
+        );
+
+        // Formats with a length prefix need the exact length upfront.
+        let bytes = postcard::to_allocvec(&list.next()).unwrap();
+        let values: Vec<String> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(vec!["b", "c", "a"], values);
+    }
+
     #[test]
     fn debug() {
         let list = LinkedList::<String>::new();
@@ -365,6 +747,23 @@ mod tests {
         assert_eq!(r#"["b", "c", "d", "a"]"#, format!("{:?}", list.next()));
     }
 
+    #[test]
+    fn view_follows_list() {
+        let list = LinkedList::<String>::new();
+        let a = list.push_back("a".into());
+        let b = list.push_back("b".into());
+        let _c = list.push_back("c".into());
+        let next = list.next();
+        let prev = list.prev();
+
+        drop(b);
+        assert_eq!(r#"["c", "a"]"#, format!("{next:?}"));
+        drop(a);
+        assert_eq!(r#"["c"]"#, format!("{next:?}"));
+        assert_eq!(r#"["c"]"#, format!("{prev:?}"));
+        assert_eq!(r#"["c"]"#, format!("{:?}", prev.prev().prev()));
+    }
+
     #[test]
     fn current() {
         let list = LinkedList::<String>::new();
@@ -377,4 +776,31 @@ mod tests {
         assert_eq!(Some(&"d".to_string()), list.prev().current().as_deref());
         assert_eq!(Some(&"b".to_string()), list.next().current().as_deref());
     }
+
+    #[test]
+    fn last() {
+        let list = LinkedList::<String>::new();
+        assert!(list.last().is_none());
+        let _a = list.push_back("a".into());
+        assert_eq!(Some(&"a".to_string()), list.last().as_deref());
+        let _b = list.push_back("b".into());
+        assert_eq!(Some(&"b".to_string()), list.last().as_deref());
+    }
+
+    #[test]
+    #[cfg(any(feature = "std", feature = "hashbrown"))]
+    fn iter_after() {
+        let list = LinkedList::<String>::new();
+        let a = list.push_back("a".into());
+        let b = list.push_back("b".into());
+        let c = list.push_back("c".into());
+        let after = |h| {
+            list.iter_after(h)
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["b", "c"], after(&a));
+        assert_eq!(vec!["c"], after(&b));
+        assert_eq!(Vec::<String>::new(), after(&c));
+    }
 }
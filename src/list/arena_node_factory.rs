@@ -0,0 +1,374 @@
+use alloc::rc::Rc;
+use core::any::Any;
+use core::any::TypeId;
+use core::cell::Cell;
+use core::cell::RefCell;
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::mem::MaybeUninit;
+use core::num::NonZeroU32;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use std::collections::HashMap;
+
+use super::node::Node;
+use super::node_factory::NodeFactory;
+use super::node_factory::RawRef;
+
+/// Stores nodes in slabs, with one arena per thread and per value type.
+///
+/// Freed slots are reused, and pointers carry a generation counter: a pointer to a slot that was
+/// freed, and possibly reused since, resolves to nothing instead of a dangling node. Unlike other
+/// factories, [to_ref](NodeFactory::to_ref) may therefore be called with stale pointers.
+///
+/// Pointers are an index and a generation, and find their arena through a thread local. Once all
+/// the slots of a chunk are free, the chunk is released, except for one kept for reuse.
+pub struct ArenaNodeFactory<V>(PhantomData<V>);
+
+const CHUNK_SIZE: usize = 1024;
+
+struct Arena<V: 'static> {
+    chunks: RefCell<Vec<Chunk<V>>>,
+    free: RefCell<Vec<u32>>,
+    // A vacant chunk kept allocated, so that a list growing and shrinking around a chunk boundary
+    // does not allocate and release a chunk every time.
+    spare: Cell<Option<usize>>,
+}
+
+struct Chunk<V: 'static> {
+    // Chunks are never resized, so slots have stable addresses. They are kept as raw pointers
+    // rather than boxes: nodes are aliased by pointers that outlive any borrow of `chunks`, and
+    // moving a box when `chunks` grows would invalidate them.
+    slots: Option<NonNull<[Slot<V>]>>,
+    live: usize,
+    // The generation of the slots when the chunk is allocated. It is raised past the generations
+    // of the slots when the chunk is released, so that stale pointers stay stale.
+    generation: NonZeroU32,
+}
+
+struct Slot<V: 'static> {
+    generation: Cell<NonZeroU32>,
+    node: UnsafeCell<MaybeUninit<Node<ArenaNodeFactory<V>>>>,
+}
+
+/// Points to a node in the [Arena] of its thread: an index in the arena and the generation of the
+/// slot.
+pub struct ArenaPointer<V: 'static> {
+    index: u32,
+    generation: NonZeroU32,
+    value: PhantomData<fn() -> V>,
+}
+
+/// Owns a node in an [Arena], and keeps the arena alive.
+pub struct ArenaHandle<V: 'static> {
+    arena: Rc<Arena<V>>,
+    index: u32,
+}
+
+thread_local! {
+    static ARENAS: RefCell<HashMap<TypeId, Rc<dyn Any>>> = RefCell::default();
+}
+
+impl<V: 'static> Arena<V> {
+    fn get() -> Rc<Self> {
+        let arena = ARENAS.with(|arenas| {
+            let mut arenas = arenas.borrow_mut();
+            let arena = arenas.entry(TypeId::of::<V>()).or_insert_with(|| {
+                Rc::new(Self {
+                    chunks: Default::default(),
+                    free: Default::default(),
+                    spare: Default::default(),
+                })
+            });
+            arena.clone()
+        });
+        arena.downcast().ok().unwrap()
+    }
+
+    /// Calls `f` with the arena of this thread, if there is one.
+    fn with<R>(f: impl FnOnce(&Self) -> R) -> Option<R> {
+        ARENAS.with(|arenas| {
+            let arenas = arenas.borrow();
+            let arena = arenas.get(&TypeId::of::<V>())?.downcast_ref()?;
+            Some(f(arena))
+        })
+    }
+
+    /// Returns the slot at `index`, or `None` if its chunk is not allocated.
+    fn try_slot(&self, index: u32) -> Option<*const Slot<V>> {
+        let index = index as usize;
+        let chunk = self.chunks.borrow().get(index / CHUNK_SIZE)?.slots?;
+        Some(unsafe { chunk.cast::<Slot<V>>().as_ptr().add(index % CHUNK_SIZE) })
+    }
+
+    fn slot(&self, index: u32) -> *const Slot<V> {
+        self.try_slot(index).unwrap()
+    }
+
+    fn alloc(&self, node: Node<ArenaNodeFactory<V>>) -> u32 {
+        let free = self.free.borrow_mut().pop();
+        let index = free.unwrap_or_else(|| self.alloc_chunk());
+        let chunk = index as usize / CHUNK_SIZE;
+        self.chunks.borrow_mut()[chunk].live += 1;
+        if self.spare.get() == Some(chunk) {
+            self.spare.set(None);
+        }
+        let slot = unsafe { &*self.slot(index) };
+        unsafe { (*slot.node.get()).write(node) };
+        index
+    }
+
+    /// Allocates a chunk, in place of a released one if any, and returns its first slot. The other
+    /// slots are added to the free list.
+    fn alloc_chunk(&self) -> u32 {
+        let mut chunks = self.chunks.borrow_mut();
+        let position = chunks.iter().position(|chunk| chunk.slots.is_none());
+        let position = position.unwrap_or_else(|| {
+            chunks.push(Chunk {
+                slots: None,
+                live: 0,
+                generation: NonZeroU32::MIN,
+            });
+            chunks.len() - 1
+        });
+        let chunk = &mut chunks[position];
+        let slots = (0..CHUNK_SIZE).map(|_| Slot {
+            generation: Cell::new(chunk.generation),
+            node: UnsafeCell::new(MaybeUninit::uninit()),
+        });
+        let slots = Box::into_raw(slots.collect::<Box<[_]>>());
+        chunk.slots = Some(unsafe { NonNull::new_unchecked(slots) });
+        let index = position
+            .checked_mul(CHUNK_SIZE)
+            .and_then(|index| u32::try_from(index).ok())
+            .expect("Too many nodes in the arena");
+        self.free
+            .borrow_mut()
+            .extend((index + 1..index + CHUNK_SIZE as u32).rev());
+        index
+    }
+
+    /// Moves the node out of its slot, and frees the slot.
+    ///
+    /// SAFETY: the slot at `index` must hold a node, and it must not be used afterwards.
+    unsafe fn free(&self, index: u32) -> Node<ArenaNodeFactory<V>> {
+        let slot = unsafe { &*self.slot(index) };
+        let node = unsafe { (*slot.node.get()).assume_init_read() };
+        let generation = slot.generation.get().checked_add(1);
+        slot.generation.set(generation.unwrap_or(NonZeroU32::MIN));
+        self.free.borrow_mut().push(index);
+        let chunk = index as usize / CHUNK_SIZE;
+        let live = {
+            let mut chunks = self.chunks.borrow_mut();
+            chunks[chunk].live -= 1;
+            chunks[chunk].live
+        };
+        if live == 0 {
+            match self.spare.get() {
+                Some(spare) if spare != chunk => self.release_chunk(chunk),
+                _ => self.spare.set(Some(chunk)),
+            }
+        }
+        node
+    }
+
+    /// Releases a chunk whose slots are all free, and removes them from the free list.
+    fn release_chunk(&self, position: usize) {
+        let mut chunks = self.chunks.borrow_mut();
+        let chunk = &mut chunks[position];
+        let slots = unsafe { Box::from_raw(chunk.slots.take().unwrap().as_ptr()) };
+        let generation = slots.iter().map(|slot| slot.generation.get()).max();
+        chunk.generation = generation.unwrap_or(chunk.generation);
+        drop(slots);
+        self.free
+            .borrow_mut()
+            .retain(|&index| index as usize / CHUNK_SIZE != position);
+    }
+}
+
+impl<V: 'static> Drop for Arena<V> {
+    fn drop(&mut self) {
+        // Handles keep the arena alive, so all the slots were freed already.
+        for chunk in self.chunks.get_mut().drain(..) {
+            if let Some(slots) = chunk.slots {
+                drop(unsafe { Box::from_raw(slots.as_ptr()) })
+            }
+        }
+    }
+}
+
+impl<V: 'static> Deref for ArenaHandle<V> {
+    type Target = Node<ArenaNodeFactory<V>>;
+
+    fn deref(&self) -> &Self::Target {
+        let slot = unsafe { &*self.arena.slot(self.index) };
+        unsafe { (*slot.node.get()).assume_init_ref() }
+    }
+}
+
+impl<V: 'static> Drop for ArenaHandle<V> {
+    fn drop(&mut self) {
+        // The node is dropped once the arena is no longer borrowed.
+        drop(unsafe { self.arena.free(self.index) })
+    }
+}
+
+impl<V: 'static> Clone for ArenaPointer<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V: 'static> Copy for ArenaPointer<V> {}
+
+impl<V: 'static> PartialEq for ArenaPointer<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+unsafe impl<V: 'static> NodeFactory for ArenaNodeFactory<V> {
+    type Value = V;
+    type Reference = RawRef<Self>;
+    type Pointer = Option<ArenaPointer<V>>;
+    type Handle = ArenaHandle<V>;
+
+    fn of(value: Self::Value) -> Self::Handle {
+        let arena = Arena::get();
+        let index = arena.alloc(Node::new(value));
+        ArenaHandle { arena, index }
+    }
+
+    fn into_value(handle: Self::Handle) -> Self::Value {
+        let handle = ManuallyDrop::new(handle);
+        let Node { value, .. } = unsafe { handle.arena.free(handle.index) };
+        drop(unsafe { core::ptr::read(&handle.arena) });
+        value
+    }
+
+    unsafe fn to_ref(pointer: &Self::Pointer) -> Option<Self::Reference> {
+        let pointer = pointer.as_ref()?;
+        let node = Arena::<V>::with(|arena| {
+            let slot = unsafe { &*arena.try_slot(pointer.index)? };
+            if slot.generation.get() != pointer.generation {
+                return None;
+            }
+            Some(slot.node.get())
+        });
+        Some(unsafe { RawRef::of((*node.flatten()?).as_ptr()) })
+    }
+
+    fn downgrade(pointer: &Self::Handle) -> Self::Pointer {
+        let slot = unsafe { &*pointer.arena.slot(pointer.index) };
+        Some(ArenaPointer {
+            index: pointer.index,
+            generation: slot.generation.get(),
+            value: PhantomData,
+        })
+    }
+
+    fn ptr_eq_ref(a: &Self::Reference, b: &Self::Reference) -> bool {
+        RawRef::ptr_eq(a, b)
+    }
+
+    fn ptr_eq_ptr(a: &Self::Pointer, b: &Self::Pointer) -> bool {
+        a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::node_factory::NodeFactory;
+    use super::super::LinkedList;
+    use super::Arena;
+    use super::ArenaNodeFactory;
+    use super::ArenaPointer;
+
+    type Factory = ArenaNodeFactory<String>;
+
+    #[test]
+    fn list() {
+        let list = LinkedList::<String, Factory>::new();
+        let a = list.push_back("a".into());
+        let b = list.push_back("b".into());
+        let c = list.push_back("c".into());
+        assert_eq!(vec!["a", "b", "c"], list.values().collect::<Vec<_>>());
+
+        drop(b);
+        assert_eq!(vec!["a", "c"], list.values().collect::<Vec<_>>());
+        let d = list.push_back_with_on_remove("d".into(), |d| assert_eq!("d", d));
+        list.sort_by(|x, y| y.cmp(x));
+        assert_eq!(vec!["d", "c", "a"], list.values().collect::<Vec<_>>());
+
+        drop(a);
+        drop(d);
+        assert_eq!(vec!["c"], list.values().collect::<Vec<_>>());
+        drop(c);
+        assert_eq!(Vec::<String>::new(), list.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stale_pointer() {
+        // SAFETY: the arena resolves stale pointers into `None`.
+        let to_ref = |pointer| unsafe { Factory::to_ref(pointer) };
+        let a = Factory::of("a".into());
+        let a_ptr = Factory::downgrade(&a);
+        assert_eq!("a", to_ref(&a_ptr).unwrap().value);
+
+        drop(a);
+        assert!(to_ref(&a_ptr).is_none());
+
+        // The slot is reused with a new generation.
+        let b = Factory::of("b".into());
+        let b_ptr = Factory::downgrade(&b);
+        assert!(to_ref(&a_ptr).is_none());
+        assert!(!Factory::ptr_eq_ptr(&a_ptr, &b_ptr));
+        assert_eq!("b", Factory::into_value(b));
+        assert!(to_ref(&b_ptr).is_none());
+    }
+
+    #[test]
+    fn many_chunks() {
+        let list = LinkedList::<usize, ArenaNodeFactory<usize>>::new();
+        let handles = (0..3000).map(|i| list.push_back(i)).collect::<Vec<_>>();
+        assert_eq!(
+            (0..3000).collect::<Vec<_>>(),
+            list.values().collect::<Vec<_>>()
+        );
+        drop(handles);
+        assert_eq!(0, list.iter().count());
+    }
+
+    #[test]
+    fn pointer_size() {
+        assert_eq!(8, size_of::<Option<ArenaPointer<String>>>());
+    }
+
+    #[test]
+    fn release_chunks() {
+        type Factory = ArenaNodeFactory<u64>;
+        // SAFETY: the arena resolves stale pointers into `None`.
+        let to_ref = |pointer| unsafe { Factory::to_ref(pointer) };
+        let allocated = || {
+            let arena = Arena::<u64>::get();
+            let chunks = arena.chunks.borrow();
+            chunks.iter().filter(|chunk| chunk.slots.is_some()).count()
+        };
+        let handles = (0..3000).map(Factory::of).collect::<Vec<_>>();
+        let pointers = handles.iter().map(Factory::downgrade).collect::<Vec<_>>();
+        assert_eq!(3, allocated());
+
+        // One vacant chunk is kept for reuse, the others are released.
+        drop(handles);
+        assert_eq!(1, allocated());
+        assert!(pointers.iter().all(|pointer| to_ref(pointer).is_none()));
+
+        // Released chunks are allocated again, and stale pointers stay stale.
+        let handles = (0..3000).map(Factory::of).collect::<Vec<_>>();
+        assert_eq!(3, allocated());
+        assert!(pointers.iter().all(|pointer| to_ref(pointer).is_none()));
+        let values = handles.iter().map(|handle| handle.value);
+        assert_eq!((0..3000).collect::<Vec<_>>(), values.collect::<Vec<_>>());
+    }
+}
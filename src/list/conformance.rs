@@ -0,0 +1,197 @@
+//! Checks that a [NodeFactory] upholds its contract.
+//!
+//! ```
+//! use linked::list::conformance;
+//! use linked::list::BoxNodeFactory;
+//!
+//! conformance::check::<BoxNodeFactory<String>>(|i| i.to_string());
+//! ```
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::fmt::Debug;
+
+use super::LinkedList;
+use super::NodeFactory;
+
+const NODES: usize = 8;
+
+/// Runs the conformance suite against `F`, panicking on the first violation.
+///
+/// `make` builds the value of the `i`-th node. Values must be pairwise distinct.
+pub fn check<F>(make: impl Fn(usize) -> F::Value)
+where
+    F: NodeFactory,
+    F::Value: PartialEq + Debug + 'static,
+{
+    pointers::<F>(&make);
+    into_value::<F>(&make);
+    list::<F>(&make);
+    split_and_move::<F>(&make);
+    on_remove::<F>(&make);
+}
+
+fn pointers<F>(make: &impl Fn(usize) -> F::Value)
+where
+    F: NodeFactory,
+    F::Value: PartialEq + Debug,
+{
+    // SAFETY: the pointers are resolved while `a` and `b`, declared below, are alive.
+    let to_ref = |pointer: &F::Pointer| unsafe { F::to_ref(pointer) };
+    let null = F::Pointer::default();
+    assert!(to_ref(&null).is_none(), "The default pointer resolves");
+    assert!(F::ptr_eq_ptr(&null, &null.clone()));
+
+    let a = F::of(make(0));
+    let b = F::of(make(1));
+    let a_ptr = F::downgrade(&a);
+    let b_ptr = F::downgrade(&b);
+    assert!(F::ptr_eq_ptr(&a_ptr, &F::downgrade(&a)));
+    assert!(F::ptr_eq_ptr(&a_ptr, &a_ptr.clone()));
+    assert!(!F::ptr_eq_ptr(&a_ptr, &b_ptr));
+    assert!(!F::ptr_eq_ptr(&a_ptr, &null));
+
+    let a_ref = to_ref(&a_ptr).expect("The pointer to a live node does not resolve");
+    let b_ref = to_ref(&b_ptr).expect("The pointer to a live node does not resolve");
+    assert!(
+        core::ptr::eq(&*a_ref, &*a),
+        "The reference is not to the node"
+    );
+    assert!(F::ptr_eq_ref(&a_ref, &a_ref.clone()));
+    assert!(F::ptr_eq_ref(&a_ref, &to_ref(&a_ptr).unwrap()));
+    assert!(!F::ptr_eq_ref(&a_ref, &b_ref));
+    assert_eq!(make(0), a_ref.value);
+    assert_eq!(make(1), b_ref.value);
+    drop((a_ref, b_ref));
+
+    // The node stays in place when its handle moves.
+    let address: *const _ = &*a;
+    let moved = Box::new(a);
+    assert!(core::ptr::eq(address, &**moved), "The node moved");
+    assert_eq!(make(0), to_ref(&a_ptr).unwrap().value);
+}
+
+fn into_value<F>(make: &impl Fn(usize) -> F::Value)
+where
+    F: NodeFactory,
+    F::Value: PartialEq + Debug,
+{
+    let handles = (0..3).map(|i| F::of(make(i))).collect::<Vec<_>>();
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(make(i), F::into_value(handle));
+    }
+}
+
+/// The indices of the values of `list`, from front to back.
+fn indices<F>(list: &LinkedList<F::Value, F>, make: &impl Fn(usize) -> F::Value) -> Vec<usize>
+where
+    F: NodeFactory,
+    F::Value: PartialEq,
+{
+    let index = |value: &F::Value| (0..NODES).find(|&i| make(i) == *value).unwrap();
+    list.iter().map(|value| index(&value)).collect()
+}
+
+fn list<F>(make: &impl Fn(usize) -> F::Value)
+where
+    F: NodeFactory,
+    F::Value: PartialEq + Debug,
+{
+    let list = LinkedList::<F::Value, F>::new();
+    let mut handles = (0..NODES)
+        .map(|i| Some(list.push_back(make(i))))
+        .collect::<Vec<_>>();
+    let index = |value: &F::Value| (0..NODES).find(|&i| make(i) == *value).unwrap();
+    assert_eq!((0..NODES).collect::<Vec<_>>(), indices(&list, make));
+
+    // Drop the first, the last, and a node in the middle.
+    for i in [0, NODES - 1, 3] {
+        handles[i] = None;
+    }
+    assert_eq!(vec![1, 2, 4, 5, 6], indices(&list, make));
+
+    list.reverse();
+    assert_eq!(vec![6, 5, 4, 2, 1], indices(&list, make));
+
+    list.sort_by_key(index);
+    assert_eq!(vec![1, 2, 4, 5, 6], indices(&list, make));
+
+    list.retain(|value| index(value) % 2 == 0);
+    assert_eq!(vec![2, 4, 6], indices(&list, make));
+
+    drop(handles);
+    assert_eq!(0, list.iter().count());
+}
+
+fn split_and_move<F>(make: &impl Fn(usize) -> F::Value)
+where
+    F: NodeFactory,
+    F::Value: PartialEq + Debug,
+{
+    let list = LinkedList::<F::Value, F>::new();
+    let handles = (0..6).map(|i| list.push_back(make(i))).collect::<Vec<_>>();
+
+    let tail = list.split_off_at(&handles[3]);
+    assert_eq!(vec![0, 1, 2], indices(&list, make));
+    assert_eq!(vec![3, 4, 5], indices(&tail, make));
+
+    handles[1].move_to_list(&tail);
+    assert_eq!(vec![0, 2], indices(&list, make));
+    assert_eq!(vec![3, 4, 5, 1], indices(&tail, make));
+
+    list.append(&tail);
+    assert_eq!(vec![0, 2, 3, 4, 5, 1], indices(&list, make));
+    assert_eq!(0, tail.iter().count());
+
+    // The lists may be dropped before the handles.
+    drop(list);
+    drop(tail);
+    drop(handles);
+}
+
+fn on_remove<F>(make: &impl Fn(usize) -> F::Value)
+where
+    F: NodeFactory,
+    F::Value: PartialEq + Debug + 'static,
+{
+    let list = LinkedList::<F::Value, F>::new();
+    let removed = Rc::new(Cell::new(false));
+    let handle = list.push_back_with_on_remove(make(0), {
+        let removed = removed.clone();
+        let expected = make(0);
+        move |value| {
+            assert_eq!(expected, value);
+            removed.set(true);
+        }
+    });
+    drop(handle);
+    assert!(removed.get(), "The value was not given back");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    #[cfg(feature = "std")]
+    use crate::list::ArenaNodeFactory;
+    use crate::list::BoxNodeFactory;
+    use crate::list::RcNodeFactory;
+
+    #[test]
+    fn rc_node_factory() {
+        check::<RcNodeFactory<String>>(|i| i.to_string());
+    }
+
+    #[test]
+    fn box_node_factory() {
+        check::<BoxNodeFactory<String>>(|i| i.to_string());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn arena_node_factory() {
+        check::<ArenaNodeFactory<String>>(|i| i.to_string());
+    }
+}
@@ -1,49 +1,50 @@
-use std::ops::Deref;
-use std::rc::Rc;
+use alloc::boxed::Box;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
 
 use super::implem::LinkedListImpl;
 use super::node_factory::NodeFactory;
-use super::with_value;
+use super::LinkedList;
 
 #[must_use]
 pub struct Handle<F: NodeFactory> {
-    pub(super) list: Rc<LinkedListImpl<F>>,
-    pub(super) node: F::Handle,
+    pub(super) node: ManuallyDrop<F::Handle>,
+    /// Boxed twice so that handles without a callback only pay for a thin pointer.
+    pub(super) on_remove: Option<Box<OnRemove<F::Value>>>,
 }
 
+pub(super) type OnRemove<V> = Box<dyn FnOnce(V)>;
+
 impl<F: NodeFactory> Handle<F> {
     pub fn value(&self) -> &F::Value {
         &self.node.value
     }
+
+    /// Unlinks the node from its current list and pushes it to the back of `list`.
+    ///
+    /// The handle and the value stay the same.
+    pub fn move_to_list(&self, list: &LinkedList<F::Value, F>) {
+        LinkedListImpl::detach(&self.node);
+        list.list.link_back(&self.node, F::downgrade(&self.node));
+    }
 }
 
 impl<F: NodeFactory> Drop for Handle<F> {
     fn drop(&mut self) {
-        let prev_ptr = with_value(&self.node.prev, F::Pointer::clone);
-        let node_ptr = F::downgrade(&self.node);
-        if F::ptr_eq_ptr(&prev_ptr, &node_ptr) {
-            self.list.node.set(F::Pointer::default());
-            return;
-        }
-        let next_ptr = with_value(&self.node.next, F::Pointer::clone);
-        match (F::to_ref(&prev_ptr), F::to_ref(&next_ptr)) {
-            (Some(prev_ref), Some(next_ref)) => {
-                // prev <-> self <-> next <-> prev
-                // prev          <-> next <-> prev
-                if F::ptr_eq_ptr(&with_value(&self.list.node, F::Pointer::clone), &node_ptr) {
-                    self.list.node.set(next_ptr.clone());
-                }
-                prev_ref.next.set(next_ptr);
-                next_ref.prev.set(prev_ptr);
-            }
-            (None, None) => {}
-            _ => unreachable!(),
+        LinkedListImpl::detach(&self.node);
+
+        // SAFETY: `self.node` is never used again.
+        let node = unsafe { ManuallyDrop::take(&mut self.node) };
+        if let Some(on_remove) = self.on_remove.take() {
+            on_remove(F::into_value(node));
         }
     }
 }
 
-impl<V: std::fmt::Debug, F: NodeFactory<Value = V>> std::fmt::Debug for Handle<F> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("Handle").field((&self.node).deref()).finish()
+impl<V: core::fmt::Debug, F: NodeFactory<Value = V>> core::fmt::Debug for Handle<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Handle")
+            .field(self.node.deref().deref())
+            .finish()
     }
 }
@@ -1,9 +1,14 @@
-use std::cell::Cell;
-use std::marker::PhantomData;
-use std::rc::Rc;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::cmp::Ordering;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
 
 use super::handle::Handle;
 use super::iterator::NodeIterator;
+use super::node::Node;
+use super::node_factory::resolve;
 use super::node_factory::NodeFactory;
 use super::node_ref::NodeRef;
 use super::with_value;
@@ -13,16 +18,27 @@ pub(super) struct LinkedListImpl<F: NodeFactory> {
     _phantom: PhantomData<F>,
 }
 
+// The anchor node of a list holds a back-reference to the list, so the list's anchor can be
+// updated when that node is detached. Only the anchor holds it, so moving nodes between lists only
+// needs to move the back-references of the anchors.
+
 impl<F: NodeFactory> LinkedListImpl<F> {
     pub fn push_back(self: &Rc<Self>, value: F::Value) -> Handle<F> {
         let new_ref = F::of(value);
-        let new_ptr = F::downgrade(&new_ref);
+        self.link_back(&new_ref, F::downgrade(&new_ref));
+        Handle {
+            node: ManuallyDrop::new(new_ref),
+            on_remove: None,
+        }
+    }
+
+    pub fn link_back(self: &Rc<Self>, new_ref: &Node<F>, new_ptr: F::Pointer) {
         let node_ptr = with_value(&self.node, F::Pointer::clone);
-        if let Some(node_ref) = F::to_ref(&node_ptr) {
+        if let Some(node_ref) = resolve::<F>(&node_ptr) {
             // prev     <->     self <-> next <-> prev
             // prev <-> new <-> self <-> next <-> prev
             let prev_ptr = with_value(&node_ref.prev, F::Pointer::clone);
-            let prev_ref = F::to_ref(&prev_ptr).unwrap();
+            let prev_ref = resolve::<F>(&prev_ptr).unwrap();
             new_ref.prev.set(prev_ptr);
             prev_ref.next.set(new_ptr.clone());
             new_ref.next.set(node_ptr);
@@ -31,51 +47,246 @@ impl<F: NodeFactory> LinkedListImpl<F> {
             self.node.set(new_ptr.clone());
             new_ref.prev.set(new_ptr.clone());
             new_ref.next.set(new_ptr);
-        }
-        Handle {
-            list: self.clone(),
-            node: new_ref,
+            new_ref.list.set(Some(self.clone()));
         }
     }
 
-    pub fn prev(self: &Rc<Self>) -> Rc<Self> {
-        let node = with_value(&self.node, F::Pointer::clone);
-        if let Some(node) = F::to_ref(&node) {
-            let prev = with_value(&node.prev, F::Pointer::clone);
-            Rc::new(LinkedListImpl {
-                node: Cell::new(prev),
-                _phantom: PhantomData,
-            })
+    /// Unlinks `node` from its list. The node's neighbors are reset, so detaching it again is a
+    /// no-op.
+    ///
+    /// If `node` is the anchor of its list, the list's back-reference moves to the next node.
+    pub fn detach(node: &Node<F>) {
+        let prev_ptr = with_value(&node.prev, F::Pointer::clone);
+        let Some(prev_ref) = resolve::<F>(&prev_ptr) else {
+            return;
+        };
+        let node_ptr = with_value(&prev_ref.next, F::Pointer::clone);
+        let list = node.list.take();
+        if F::ptr_eq_ptr(&prev_ptr, &node_ptr) {
+            if let Some(list) = &list {
+                list.node.set(F::Pointer::default());
+            }
         } else {
-            self.clone()
+            // prev <-> self <-> next <-> prev
+            // prev          <-> next <-> prev
+            let next_ptr = with_value(&node.next, F::Pointer::clone);
+            let next_ref = resolve::<F>(&next_ptr).unwrap();
+            if let Some(list) = list {
+                list.node.set(next_ptr.clone());
+                next_ref.list.set(Some(list));
+            }
+            prev_ref.next.set(next_ptr);
+            next_ref.prev.set(prev_ptr);
+        }
+        node.prev.set(F::Pointer::default());
+        node.next.set(F::Pointer::default());
+    }
+
+    pub fn retain(&self, mut keep: impl FnMut(&F::Value) -> bool) {
+        let nodes = self.iter(0).collect::<Vec<_>>();
+        for node in nodes {
+            if !keep(&node) {
+                Self::detach(&node.0);
+            }
         }
     }
 
-    pub fn next(self: &Rc<Self>) -> Rc<Self> {
-        let node = with_value(&self.node, F::Pointer::clone);
-        if let Some(node) = F::to_ref(&node) {
-            let next = with_value(&node.next, F::Pointer::clone);
-            Rc::new(LinkedListImpl {
-                node: Cell::new(next),
-                _phantom: PhantomData,
-            })
+    /// Moves all the nodes of `other` to the back of this list.
+    pub fn append(self: &Rc<Self>, other: &Rc<Self>) {
+        if Rc::ptr_eq(self, other) {
+            return;
+        }
+        let Some(other_first) = with_value(&other.node, resolve::<F>) else {
+            return;
+        };
+        let other_first_ptr = other.node.take();
+        let _other = other.take_back_ref(&other_first);
+        let Some(first) = with_value(&self.node, resolve::<F>) else {
+            self.node.set(other_first_ptr);
+            other_first.list.set(Some(self.clone()));
+            return;
+        };
+
+        // first <-> .. <-> last <-> first
+        // first <-> .. <-> last <-> other_first <-> .. <-> other_last <-> first
+        let first_ptr = with_value(&self.node, F::Pointer::clone);
+        let last_ptr = with_value(&first.prev, F::Pointer::clone);
+        let last = resolve::<F>(&last_ptr).unwrap();
+        let other_last_ptr = with_value(&other_first.prev, F::Pointer::clone);
+        let other_last = resolve::<F>(&other_last_ptr).unwrap();
+        last.next.set(other_first_ptr.clone());
+        other_first.prev.set(last_ptr);
+        other_last.next.set(first_ptr);
+        first.prev.set(other_last_ptr);
+    }
+
+    /// Moves the nodes from `at` to the back of this list into a new list.
+    ///
+    /// Panics if `at` does not belong to this list.
+    pub fn split_off(self: &Rc<Self>, at: &Node<F>, at_ptr: F::Pointer) -> Rc<Self> {
+        assert!(
+            self.contains(&at_ptr),
+            "The handle does not belong to this list"
+        );
+        let result = Rc::new(Self::default());
+        let first_ptr = with_value(&self.node, F::Pointer::clone);
+        let first = resolve::<F>(&first_ptr).unwrap();
+        if F::ptr_eq_ptr(&first_ptr, &at_ptr) {
+            let _self = self.take_back_ref(&first);
+            self.node.set(F::Pointer::default());
+            result.node.set(at_ptr);
+            first.list.set(Some(result.clone()));
+            return result;
+        }
+
+        // first <-> .. <-> before <-> at <-> .. <-> last <-> first
+        // first <-> .. <-> before <-> first
+        //                             at <-> .. <-> last <-> at
+        let last_ptr = with_value(&first.prev, F::Pointer::clone);
+        let last = resolve::<F>(&last_ptr).unwrap();
+        let before_ptr = with_value(&at.prev, F::Pointer::clone);
+        let before = resolve::<F>(&before_ptr).unwrap();
+        before.next.set(first_ptr);
+        first.prev.set(before_ptr);
+        last.next.set(at_ptr.clone());
+        at.prev.set(last_ptr);
+        result.node.set(at_ptr);
+        at.list.set(Some(result.clone()));
+        result
+    }
+
+    /// Exchanges the nodes of both lists.
+    pub fn swap(self: &Rc<Self>, other: &Rc<Self>) {
+        let a = self.node.take();
+        let b = other.node.take();
+        if let Some(a) = resolve::<F>(&a) {
+            a.list.set(Some(other.clone()));
+        }
+        if let Some(b) = resolve::<F>(&b) {
+            b.list.set(Some(self.clone()));
+        }
+        self.node.set(b);
+        other.node.set(a);
+    }
+
+    /// Checks whether the node at `ptr` belongs to this list, by walking from that node to the
+    /// anchor of this list.
+    fn contains(&self, ptr: &F::Pointer) -> bool {
+        let anchor = with_value(&self.node, F::Pointer::clone);
+        let mut next = ptr.clone();
+        loop {
+            if F::ptr_eq_ptr(&next, &anchor) {
+                return true;
+            }
+            let Some(node) = resolve::<F>(&next) else {
+                return false;
+            };
+            next = with_value(&node.next, F::Pointer::clone);
+            if F::ptr_eq_ptr(&next, ptr) {
+                return false;
+            }
+        }
+    }
+
+    /// Takes the back-reference held by `node` if it refers to this list.
+    fn take_back_ref(&self, node: &Node<F>) -> Option<Rc<Self>> {
+        let list = node.list.take();
+        if list
+            .as_ref()
+            .is_some_and(|list| core::ptr::eq(Rc::as_ptr(list), self))
+        {
+            list
         } else {
-            self.clone()
+            node.list.set(list);
+            None
+        }
+    }
+
+    /// Sorts the nodes by relinking them. The sort is stable.
+    pub fn sort_by(&self, mut compare: impl FnMut(&F::Value, &F::Value) -> Ordering) {
+        let mut nodes = self.iter_with_pointers().collect::<Vec<_>>();
+        nodes.sort_by(|(a, _), (b, _)| compare(a, b));
+        self.relink(nodes);
+    }
+
+    pub fn reverse(&self) {
+        let mut nodes = self.iter_with_pointers().collect::<Vec<_>>();
+        nodes.reverse();
+        self.relink(nodes);
+    }
+
+    fn iter_with_pointers(&self) -> impl Iterator<Item = (NodeRef<F>, F::Pointer)> {
+        self.iter(0).map(|node| {
+            // Nodes don't know their own pointer, but their predecessor does.
+            let prev = with_value(&node.0.prev, resolve::<F>).unwrap();
+            let ptr = with_value(&prev.next, F::Pointer::clone);
+            (node, ptr)
+        })
+    }
+
+    fn relink(&self, nodes: Vec<(NodeRef<F>, F::Pointer)>) {
+        let Some((first, first_ptr)) = nodes.first() else {
+            return;
+        };
+        let list = with_value(&self.node, resolve::<F>).and_then(|node| self.take_back_ref(&node));
+        let len = nodes.len();
+        for (i, (node, _)) in nodes.iter().enumerate() {
+            node.0.prev.set(nodes[(i + len - 1) % len].1.clone());
+            node.0.next.set(nodes[(i + 1) % len].1.clone());
+        }
+        self.node.set(first_ptr.clone());
+        if list.is_some() {
+            first.0.list.set(list);
         }
     }
 }
 
 impl<F: NodeFactory> LinkedListImpl<F> {
-    pub fn iter(&self) -> impl Iterator<Item = NodeRef<F>> {
-        let next = with_value(&self.node, F::to_ref);
+    /// The node `rotation` steps after the anchor, or before it if `rotation` is negative.
+    ///
+    /// Rotated views are resolved from the anchor each time they are used, so that they never keep
+    /// a pointer to a node whose handle may be dropped.
+    fn start(&self, rotation: isize) -> Option<F::Reference> {
+        let mut node = with_value(&self.node, resolve::<F>)?;
+        for _ in 0..rotation.unsigned_abs() {
+            let next = match rotation < 0 {
+                true => with_value(&node.prev, resolve::<F>),
+                false => with_value(&node.next, resolve::<F>),
+            };
+            node = next.unwrap();
+        }
+        Some(node)
+    }
+
+    pub fn iter(&self, rotation: isize) -> impl Iterator<Item = NodeRef<F>> {
+        let next = self.start(rotation);
         let stop = next
             .as_ref()
-            .and_then(|next| with_value(&next.prev, F::to_ref));
+            .and_then(|next| with_value(&next.prev, resolve::<F>));
         NodeIterator::<F> { next, stop }
     }
 
-    pub fn current(&self) -> Option<NodeRef<F>> {
-        with_value(&self.node, F::to_ref).map(NodeRef::of)
+    #[cfg(any(feature = "std", feature = "hashbrown"))]
+    pub fn iter_after(&self, handle: &Handle<F>) -> impl Iterator<Item = NodeRef<F>> {
+        let last_ptr = with_value(&self.node, resolve::<F>)
+            .map(|node| with_value(&node.prev, F::Pointer::clone))
+            .unwrap_or_default();
+        let next = if F::ptr_eq_ptr(&last_ptr, &F::downgrade(&handle.node)) {
+            None
+        } else {
+            with_value(&handle.node.next, resolve::<F>)
+        };
+        let stop = resolve::<F>(&last_ptr);
+        NodeIterator::<F> { next, stop }
+    }
+
+    pub fn current(&self, rotation: isize) -> Option<NodeRef<F>> {
+        self.start(rotation).map(NodeRef::of)
+    }
+
+    pub fn last(&self, rotation: isize) -> Option<NodeRef<F>> {
+        let node = self.start(rotation)?;
+        with_value(&node.prev, resolve::<F>).map(NodeRef::of)
     }
 }
 
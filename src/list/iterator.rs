@@ -1,3 +1,4 @@
+use super::node_factory::resolve;
 use super::node_factory::NodeFactory;
 use super::node_ref::NodeRef;
 use super::with_value;
@@ -17,7 +18,7 @@ impl<F: NodeFactory> Iterator for NodeIterator<F> {
         let end = self
             .stop
             .as_ref()
-            .map(|stop| F::ptr_eq_ref(&next, &stop))
+            .map(|stop| F::ptr_eq_ref(next, stop))
             .unwrap_or(true);
 
         let result = Some(NodeRef::of(next.clone()));
@@ -25,10 +26,10 @@ impl<F: NodeFactory> Iterator for NodeIterator<F> {
         if end {
             self.next = None;
         } else {
-            let next = with_value(&next.next, F::to_ref);
+            let next = with_value(&next.next, resolve::<F>);
             self.next = next;
         }
 
-        return result;
+        result
     }
 }
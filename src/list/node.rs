@@ -1,20 +1,44 @@
-use std::cell::Cell;
+use alloc::rc::Rc;
+use core::cell::Cell;
 
+use super::implem::LinkedListImpl;
+use super::node_factory::resolve;
 use super::node_factory::NodeFactory;
 use super::with_value;
 
+/// A node of a [LinkedList](super::LinkedList), allocated by a [NodeFactory].
 pub struct Node<F: NodeFactory> {
     pub(super) prev: Cell<F::Pointer>,
     pub(super) value: F::Value,
     pub(super) next: Cell<F::Pointer>,
+    pub(super) list: Cell<Option<Rc<LinkedListImpl<F>>>>,
 }
 
-impl<V: std::fmt::Debug, F: NodeFactory<Value = V>> std::fmt::Debug for Node<F> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<F: NodeFactory> Node<F> {
+    pub fn new(value: F::Value) -> Self {
+        Self {
+            prev: Default::default(),
+            value,
+            next: Default::default(),
+            list: Default::default(),
+        }
+    }
+
+    pub fn value(&self) -> &F::Value {
+        &self.value
+    }
+
+    pub fn into_value(self) -> F::Value {
+        self.value
+    }
+}
+
+impl<V: core::fmt::Debug, F: NodeFactory<Value = V>> core::fmt::Debug for Node<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Node")
-            .field("prev", &with_value(&self.prev, F::to_ref).unwrap().value)
+            .field("prev", &with_value(&self.prev, resolve::<F>).unwrap().value)
             .field("value", &self.value)
-            .field("next", &with_value(&self.next, F::to_ref).unwrap().value)
+            .field("next", &with_value(&self.next, resolve::<F>).unwrap().value)
             .finish()
     }
 }
@@ -1,55 +1,120 @@
-use std::marker::PhantomData;
-use std::ops::Deref;
-use std::rc::Rc;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::NonNull;
 
 use super::node::Node;
 
-pub trait NodeFactory: Sized {
+/// Allocates the nodes of a [LinkedList](super::LinkedList), and defines how nodes point to each
+/// other.
+///
+/// A node is owned by a [Handle](NodeFactory::Handle). Nodes point to their neighbors through
+/// [Pointer](NodeFactory::Pointer)s, which the list resolves into
+/// [Reference](NodeFactory::Reference)s to read or relink the nodes.
+///
+/// Run [conformance::check](super::conformance::check) in the tests of any implementation.
+///
+/// # Safety
+///
+/// The list dereferences the references it obtains from the factory without further checks, so
+/// implementations must uphold the following, for as long as the handle of a node is alive:
+/// - The node does not move, even when its handle moves. [of](NodeFactory::of) returns a handle to
+///   a new node, and [into_value](NodeFactory::into_value) frees the node of the handle.
+/// - [downgrade](NodeFactory::downgrade) returns pointers that [to_ref](NodeFactory::to_ref)
+///   resolves into references to the same node. References, and their clones, keep dereferencing
+///   to that node.
+/// - [ptr_eq_ref](NodeFactory::ptr_eq_ref) and [ptr_eq_ptr](NodeFactory::ptr_eq_ptr) return true
+///   exactly when both arguments designate the same node.
+/// - The default pointer designates no node: [to_ref](NodeFactory::to_ref) resolves it into
+///   `None`, and it is only equal to itself.
+///
+/// The list never resolves a pointer to a node whose handle was dropped: it only follows the
+/// pointers between linked nodes, and rotated views returned by
+/// [LinkedList::prev](super::LinkedList::prev) are resolved from the first node of the list each
+/// time they are used, rather than keeping a pointer to a node. Implementations may still detect
+/// stale pointers and resolve them into `None`, like [ArenaNodeFactory](super::ArenaNodeFactory)
+/// does.
+pub unsafe trait NodeFactory: Sized {
     type Value;
     type Reference: Clone + Deref<Target = Node<Self>>; // Rc<Node<V>>
     type Pointer: Clone + Default; // Weak<Node<V>>
     type Handle: Deref<Target = Node<Self>>;
 
     fn of(value: Self::Value) -> Self::Handle;
-    fn to_ref(pointer: &Self::Pointer) -> Option<Self::Reference>;
+    fn into_value(handle: Self::Handle) -> Self::Value;
+    /// Resolves a pointer into a reference to its node, or `None` for the default pointer.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` is the default pointer, or was returned by [downgrade](NodeFactory::downgrade)
+    /// for a handle that is still alive. The reference, and its clones, are only dereferenced while
+    /// that handle is alive.
+    unsafe fn to_ref(pointer: &Self::Pointer) -> Option<Self::Reference>;
     fn downgrade(pointer: &Self::Handle) -> Self::Pointer;
     fn ptr_eq_ref(a: &Self::Reference, b: &Self::Reference) -> bool;
     fn ptr_eq_ptr(a: &Self::Pointer, b: &Self::Pointer) -> bool;
 }
 
+/// Resolves a pointer held by a list.
+///
+/// A list only holds the pointers of the nodes it links, and a node is unlinked before its handle
+/// is dropped, so these pointers meet the requirements of [NodeFactory::to_ref].
+pub(super) fn resolve<F: NodeFactory>(pointer: &F::Pointer) -> Option<F::Reference> {
+    unsafe { F::to_ref(pointer) }
+}
+
 pub struct RcNodeFactory<V>(PhantomData<V>);
-pub struct RawRef<V>(*const Node<RcNodeFactory<V>>);
 
-impl<V> Clone for RawRef<V> {
+/// A [Reference](NodeFactory::Reference) implemented as a raw pointer to the node.
+pub struct RawRef<F: NodeFactory>(*const Node<F>);
+
+impl<F: NodeFactory> RawRef<F> {
+    /// # Safety
+    ///
+    /// `node` must stay valid for as long as the reference, or its clones, are dereferenced.
+    pub unsafe fn of(node: *const Node<F>) -> Self {
+        Self(node)
+    }
+
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        a.0 == b.0
+    }
+}
+
+impl<F: NodeFactory> Clone for RawRef<F> {
     fn clone(&self) -> Self {
         Self(self.0)
     }
 }
 
-impl<V> Deref for RawRef<V> {
-    type Target = Node<RcNodeFactory<V>>;
+impl<F: NodeFactory> Deref for RawRef<F> {
+    type Target = Node<F>;
 
     fn deref(&self) -> &Self::Target {
         unsafe { &*self.0 }
     }
 }
 
-impl<V> NodeFactory for RcNodeFactory<V> {
+unsafe impl<V> NodeFactory for RcNodeFactory<V> {
     type Value = V;
-    type Reference = RawRef<V>;
+    type Reference = RawRef<Self>;
     type Pointer = Option<*const Node<Self>>;
-    type Handle = Rc<Node<Self>>; // TODO: This can just be a Box<Node<Self>>
+    type Handle = Rc<Node<Self>>;
 
     fn of(value: Self::Value) -> Self::Handle {
-        Rc::new(Node {
-            prev: Default::default(),
-            value: value,
-            next: Default::default(),
-        })
+        Rc::new(Node::new(value))
+    }
+
+    fn into_value(handle: Self::Handle) -> Self::Value {
+        let Ok(Node { value, .. }) = Rc::try_unwrap(handle) else {
+            unreachable!("Node handles are never shared")
+        };
+        value
     }
 
-    fn to_ref(pointer: &Self::Pointer) -> Option<Self::Reference> {
-        pointer.map(|p| RawRef(p))
+    unsafe fn to_ref(pointer: &Self::Pointer) -> Option<Self::Reference> {
+        pointer.map(RawRef)
     }
 
     fn downgrade(pointer: &Self::Handle) -> Self::Pointer {
@@ -64,3 +129,60 @@ impl<V> NodeFactory for RcNodeFactory<V> {
         a == b
     }
 }
+
+/// Allocates each node in its own [Box], without the reference counts of [RcNodeFactory].
+pub struct BoxNodeFactory<V>(PhantomData<V>);
+
+/// Owns a [Box]-allocated node.
+///
+/// The node is kept as a raw pointer rather than a [Box]: other nodes alias it through their
+/// `prev` and `next` pointers, which a [Box] does not allow.
+pub struct BoxHandle<V>(NonNull<Node<BoxNodeFactory<V>>>);
+
+impl<V> Deref for BoxHandle<V> {
+    type Target = Node<BoxNodeFactory<V>>;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<V> Drop for BoxHandle<V> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) })
+    }
+}
+
+unsafe impl<V> NodeFactory for BoxNodeFactory<V> {
+    type Value = V;
+    type Reference = RawRef<Self>;
+    type Pointer = Option<*const Node<Self>>;
+    type Handle = BoxHandle<V>;
+
+    fn of(value: Self::Value) -> Self::Handle {
+        BoxHandle(NonNull::from(Box::leak(Box::new(Node::new(value)))))
+    }
+
+    fn into_value(handle: Self::Handle) -> Self::Value {
+        let node = unsafe { Box::from_raw(handle.0.as_ptr()) };
+        core::mem::forget(handle);
+        let Node { value, .. } = *node;
+        value
+    }
+
+    unsafe fn to_ref(pointer: &Self::Pointer) -> Option<Self::Reference> {
+        pointer.map(RawRef)
+    }
+
+    fn downgrade(pointer: &Self::Handle) -> Self::Pointer {
+        Some(pointer.0.as_ptr())
+    }
+
+    fn ptr_eq_ref(a: &Self::Reference, b: &Self::Reference) -> bool {
+        a.0 == b.0
+    }
+
+    fn ptr_eq_ptr(a: &Self::Pointer, b: &Self::Pointer) -> bool {
+        a == b
+    }
+}
@@ -1,8 +1,8 @@
-use std::ops::Deref;
+use core::ops::Deref;
 
 use super::node_factory::NodeFactory;
 
-pub struct NodeRef<F: NodeFactory>(F::Reference);
+pub struct NodeRef<F: NodeFactory>(pub(super) F::Reference);
 
 impl<F: NodeFactory> NodeRef<F> {
     pub fn of(value: F::Reference) -> Self {
@@ -18,8 +18,8 @@ impl<F: NodeFactory> Deref for NodeRef<F> {
     }
 }
 
-impl<V: std::fmt::Debug, F: NodeFactory<Value = V>> std::fmt::Debug for NodeRef<F> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<V: core::fmt::Debug, F: NodeFactory<Value = V>> core::fmt::Debug for NodeRef<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.as_ref().fmt(f)
     }
 }
@@ -1,58 +1,99 @@
-use std::ops::Deref;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::ser::SerializeSeq;
 
-use super::collectible::CollectibleValue;
-use super::collectible::IsCollectibleItem;
-use super::node_ref::NodeRef;
 use super::LinkedList;
+use super::NodeFactory;
+use crate::collectible::Collectible;
 
-impl<V> serde::Serialize for LinkedList<V>
+/// Serializes the values in order, starting from the current node: rotated views returned by
+/// [LinkedList::prev] and [LinkedList::next] serialize in their rotated order.
+impl<V, F> serde::Serialize for LinkedList<V, F>
 where
     V: serde::Serialize,
+    F: NodeFactory<Value = V>,
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let data: Vec<_> = self.iter().collect();
-        serializer.collect_seq(data.iter().map(NodeRef::deref))
+        let mut seq = serializer.serialize_seq(Some(self.iter().count()))?;
+        for value in self.iter() {
+            seq.serialize_element(value.deref())?;
+        }
+        seq.end()
     }
 }
 
 impl<'de, V> serde::Deserialize<'de> for LinkedList<V>
 where
-    V: serde::Deserialize<'de> + IsCollectibleItem<Value = V>,
+    V: serde::Deserialize<'de> + Collectible + 'static,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let data = Vec::<V>::deserialize(deserializer)?;
-        Ok(data.into_iter().map(V::from).collect())
+        deserializer.deserialize_seq(SeqVisitor::new(
+            |_| LinkedList::new(),
+            |list: &mut Self, value| list.extend([value]),
+        ))
     }
 }
 
-impl<V> serde::Serialize for CollectibleValue<V>
+/// Deserializes a sequence element by element: `init` creates the collection from the number of
+/// elements to reserve, then `push` adds each element.
+pub(crate) struct SeqVisitor<C, T, I, P> {
+    init: I,
+    push: P,
+    _phantom: PhantomData<(C, T)>,
+}
+
+impl<C, T, I, P> SeqVisitor<C, T, I, P>
 where
-    V: serde::Serialize + Clone,
+    I: FnOnce(usize) -> C,
+    P: FnMut(&mut C, T),
 {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let value: &V = &*self;
-        value.serialize(serializer)
+    pub(crate) fn new(init: I, push: P) -> Self {
+        Self {
+            init,
+            push,
+            _phantom: PhantomData,
+        }
     }
 }
 
-impl<'de, V> serde::Deserialize<'de> for CollectibleValue<V>
+impl<'de, C, T, I, P> Visitor<'de> for SeqVisitor<C, T, I, P>
 where
-    V: serde::Deserialize<'de> + Clone,
+    T: serde::Deserialize<'de>,
+    I: FnOnce(usize) -> C,
+    P: FnMut(&mut C, T),
 {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    type Value = C;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
     where
-        D: serde::Deserializer<'de>,
+        A: SeqAccess<'de>,
     {
-        let value = V::deserialize(deserializer)?;
-        Ok(CollectibleValue::from(value))
+        let mut collection = (self.init)(cautious::<T>(seq.size_hint()));
+        while let Some(item) = seq.next_element()? {
+            (self.push)(&mut collection, item);
+        }
+        Ok(collection)
     }
 }
+
+/// The number of elements to reserve for a size hint, which comes from untrusted input: like serde
+/// does for its own collections, this preallocates at most 1 MiB.
+pub(crate) fn cautious<T>(hint: Option<usize>) -> usize {
+    const MAX_PREALLOCATED_BYTES: usize = 1024 * 1024;
+    let max = MAX_PREALLOCATED_BYTES / core::mem::size_of::<T>().max(1);
+    hint.unwrap_or(0).min(max)
+}
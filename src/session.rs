@@ -0,0 +1,485 @@
+//! Serialization of the handles that point into containers.
+//!
+//! A handle cannot be serialized on its own, since it owns an entry of its container. Within a
+//! session, containers serialized with [list] or [map] give an id to each of their entries, and
+//! handles serialize as the id of their entry. Deserializing in a session reconnects the handles
+//! to the entries of the deserialized containers.
+//!
+//! A container must come before the handles that point into it, so that their ids are known when
+//! the handles are serialized and deserialized.
+//!
+//! ```
+//! use linked::list::BoxNodeFactory;
+//! use linked::list::Handle;
+//! use linked::list::LinkedList;
+//! use linked::session;
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Registry {
+//!     #[serde(with = "session::list")]
+//!     names: LinkedList<String>,
+//!     owners: Vec<Owner>,
+//! }
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Owner {
+//!     name: Handle<BoxNodeFactory<String>>,
+//! }
+//!
+//! let names = LinkedList::new();
+//! let owners = ["a", "b"].map(|name| Owner {
+//!     name: names.push_back(name.to_string()),
+//! });
+//! let registry = Registry {
+//!     names,
+//!     owners: owners.into(),
+//! };
+//!
+//! let json = session::serialize(|| serde_json::to_string(&registry)).unwrap();
+//! let (registry, unclaimed) = session::deserialize(|| serde_json::from_str::<Registry>(&json));
+//! let Registry { names, mut owners } = registry.unwrap();
+//! assert!(unclaimed.is_empty());
+//!
+//! // The owners own the entries of the deserialized list.
+//! drop(owners.remove(0));
+//! assert_eq!(vec!["b"], names.values().collect::<Vec<_>>());
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cell::RefCell;
+use core::fmt;
+use core::hash::Hash;
+use core::marker::PhantomData;
+use std::collections::HashMap;
+
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+
+use crate::hash_map;
+use crate::list::NodeFactory;
+
+thread_local! {
+    static SESSION: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+static NO_SESSION: &str = "Handles are only serialized within linked::session";
+
+static UNKNOWN_ENTRY: &str = "Unknown entry: containers must come before their handles";
+
+#[derive(Default)]
+struct Session {
+    /// The ids given to the entries serialized so far, by address.
+    ids: HashMap<*const (), u64>,
+    /// The handles of the entries deserialized so far, by id.
+    entries: HashMap<u64, Entry>,
+}
+
+struct Entry {
+    handle: Box<dyn Any>,
+    claimed: bool,
+}
+
+/// Keeps the entries that were deserialized without their handles: they are removed from their
+/// containers once this is dropped.
+#[must_use]
+pub struct Unclaimed(#[allow(unused)] Vec<Box<dyn Any>>);
+
+/// Runs `f`, which serializes containers and handles, in a new session.
+pub fn serialize<R>(f: impl FnOnce() -> R) -> R {
+    run(f).0
+}
+
+/// Runs `f`, which deserializes containers and handles, in a new session.
+pub fn deserialize<R>(f: impl FnOnce() -> R) -> (R, Unclaimed) {
+    let (result, session) = run(f);
+    let entries = session.entries.into_values();
+    let unclaimed = entries.filter(|entry| !entry.claimed);
+    (
+        result,
+        Unclaimed(unclaimed.map(|entry| entry.handle).collect()),
+    )
+}
+
+fn run<R>(f: impl FnOnce() -> R) -> (R, Session) {
+    let previous = SESSION.replace(Some(Session::default()));
+    let _restore = scopeguard::guard(previous, |previous| SESSION.set(previous));
+    let result = f();
+    (result, SESSION.take().unwrap())
+}
+
+fn with<R, E>(f: impl FnOnce(&mut Session) -> Result<R, E>) -> Result<R, E>
+where
+    E: serde::de::Error,
+{
+    SESSION.with_borrow_mut(|session| match session {
+        Some(session) => f(session),
+        None => Err(E::custom(NO_SESSION)),
+    })
+}
+
+/// Gives an id to the entry at `address`, as its container is serialized.
+fn new_id<E>(address: *const ()) -> Result<u64, E>
+where
+    E: serde::ser::Error,
+{
+    SESSION.with_borrow_mut(|session| {
+        let ids = &mut session.as_mut().ok_or_else(|| E::custom(NO_SESSION))?.ids;
+        let next = ids.len() as u64;
+        Ok(*ids.entry(address).or_insert(next))
+    })
+}
+
+/// The id given to the entry at `address` when its container was serialized.
+fn entry_id<E>(address: *const ()) -> Result<u64, E>
+where
+    E: serde::ser::Error,
+{
+    SESSION.with_borrow(|session| {
+        let ids = &session.as_ref().ok_or_else(|| E::custom(NO_SESSION))?.ids;
+        ids.get(&address)
+            .copied()
+            .ok_or_else(|| E::custom(UNKNOWN_ENTRY))
+    })
+}
+
+impl Session {
+    fn insert<E>(&mut self, id: u64, handle: Box<dyn Any>) -> Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        let entry = Entry {
+            handle,
+            claimed: false,
+        };
+        match self.entries.insert(id, entry) {
+            None => Ok(()),
+            Some(_) => Err(E::custom(format_args!("Duplicate entry id {id}"))),
+        }
+    }
+
+    /// Claims the handle of the entry `id`, stored as an `S`.
+    fn claim<S, H, E>(&mut self, id: u64, claim: impl FnOnce(&mut S) -> Option<H>) -> Result<H, E>
+    where
+        S: 'static,
+        E: serde::de::Error,
+    {
+        let entry = self.entries.get_mut(&id).ok_or_else(|| {
+            E::custom(format_args!(
+                "Unknown entry id {id}: containers must come before their handles"
+            ))
+        })?;
+        let handle = entry.handle.downcast_mut::<S>().ok_or_else(|| {
+            E::custom(format_args!(
+                "Entry {id} belongs to another kind of container"
+            ))
+        })?;
+        let handle = claim(handle).ok_or_else(|| {
+            E::custom(format_args!(
+                "The handle of entry {id} is already deserialized"
+            ))
+        })?;
+        entry.claimed = true;
+        Ok(handle)
+    }
+}
+
+impl Unclaimed {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Serializes a [LinkedList](crate::list::LinkedList) along with the ids of its entries, with
+/// `#[serde(with = "linked::session::list")]`.
+pub mod list {
+    use serde::ser::SerializeSeq;
+
+    use super::EntriesVisitor;
+    use crate::list::LinkedList;
+    use crate::list::NodeFactory;
+
+    pub fn serialize<V, F, S>(list: &LinkedList<V, F>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: serde::Serialize,
+        F: NodeFactory<Value = V>,
+        S: serde::Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(list.iter().count()))?;
+        for value in list.iter() {
+            let id = super::new_id(core::ptr::from_ref::<V>(&value).cast())?;
+            seq.serialize_element(&(id, &*value))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, V, F, D>(deserializer: D) -> Result<LinkedList<V, F>, D::Error>
+    where
+        V: serde::Deserialize<'de>,
+        F: NodeFactory<Value = V> + 'static,
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(EntriesVisitor::new(
+            LinkedList::new(),
+            |list: &LinkedList<V, F>, (id, value)| (id, Some(list.push_back(value))),
+        ))
+    }
+}
+
+/// Serializes a [LinkedHashMap](crate::hash_map::LinkedHashMap) along with the ids of its
+/// entries, with `#[serde(with = "linked::session::map")]`.
+pub mod map {
+    use core::hash::Hash;
+
+    use serde::ser::SerializeSeq;
+
+    use super::EntriesVisitor;
+    use crate::hash_map::LinkedHashMap;
+
+    pub fn serialize<K, V, S>(map: &LinkedHashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: serde::Serialize + Clone + Eq + Hash,
+        V: serde::Serialize,
+        S: serde::Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(map.len()))?;
+        let mut result = Ok(());
+        map.for_each_with_address(|key, value, address| {
+            if result.is_ok() {
+                result =
+                    super::new_id(address).and_then(|id| seq.serialize_element(&(id, key, value)));
+            }
+        });
+        result?;
+        seq.end()
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<LinkedHashMap<K, V>, D::Error>
+    where
+        K: serde::Deserialize<'de> + Clone + Eq + Hash + 'static,
+        V: serde::Deserialize<'de> + 'static,
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(EntriesVisitor::new(
+            LinkedHashMap::new(),
+            |map: &LinkedHashMap<K, V>, (id, key, value)| (id, map.insert(key, value).handle),
+        ))
+    }
+}
+
+impl<F> serde::Serialize for crate::list::Handle<F>
+where
+    F: NodeFactory,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let id = entry_id(core::ptr::from_ref(self.value()).cast())?;
+        serializer.serialize_u64(id)
+    }
+}
+
+impl<'de, F> serde::Deserialize<'de> for crate::list::Handle<F>
+where
+    F: NodeFactory + 'static,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let id = u64::deserialize(deserializer)?;
+        with(|session| session.claim(id, Option::<Self>::take))
+    }
+}
+
+impl<K, V> serde::Serialize for hash_map::Handle<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u64(entry_id(self.address())?)
+    }
+}
+
+impl<'de, K, V> serde::Deserialize<'de> for hash_map::Handle<K, V>
+where
+    K: Clone + Eq + Hash + 'static,
+    V: 'static,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let id = u64::deserialize(deserializer)?;
+        with(|session| session.claim(id, |handle: &mut Self| Some(handle.clone())))
+    }
+}
+
+/// Deserializes the entries of a container: `push` adds each element to the container, and
+/// returns its id and its handle.
+struct EntriesVisitor<C, T, H, P> {
+    container: C,
+    push: P,
+    _phantom: PhantomData<(T, H)>,
+}
+
+impl<C, T, H, P> EntriesVisitor<C, T, H, P>
+where
+    P: FnMut(&C, T) -> (u64, H),
+{
+    fn new(container: C, push: P) -> Self {
+        Self {
+            container,
+            push,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'de, C, T, H, P> Visitor<'de> for EntriesVisitor<C, T, H, P>
+where
+    T: serde::Deserialize<'de>,
+    H: 'static,
+    P: FnMut(&C, T) -> (u64, H),
+{
+    type Value = C;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of entries with their ids")
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(element) = seq.next_element()? {
+            let (id, handle) = (self.push)(&self.container, element);
+            with(|session| session.insert(id, Box::new(handle)))?;
+        }
+        Ok(self.container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    use crate::hash_map::LinkedHashMap;
+    use crate::list::BoxNodeFactory;
+    use crate::list::Handle;
+    use crate::list::LinkedList;
+
+    type Tag = Handle<BoxNodeFactory<String>>;
+    type Entry = crate::hash_map::Handle<String, u32>;
+
+    #[derive(Serialize, Deserialize)]
+    struct Graph {
+        #[serde(with = "super::list")]
+        tags: LinkedList<String>,
+        // The handles of the items hold handles into `tags`.
+        #[serde(with = "super::list")]
+        items: LinkedList<Item>,
+        #[serde(with = "super::map")]
+        index: LinkedHashMap<String, u32>,
+        owners: Vec<(Handle<BoxNodeFactory<Item>>, Entry)>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Item {
+        tags: Vec<Tag>,
+    }
+
+    fn graph() -> Graph {
+        let tags = LinkedList::new();
+        let items = LinkedList::new();
+        let index = LinkedHashMap::new();
+        let item = |names: &[&str]| Item {
+            tags: names
+                .iter()
+                .map(|n| tags.push_back(n.to_string()))
+                .collect(),
+        };
+        let a = items.push_back(item(&["x", "y"]));
+        let b = items.push_back(item(&["z"]));
+        let i = index.insert("i".into(), 1).handle;
+        let owners = vec![(a, i.clone()), (b, i)];
+        Graph {
+            tags,
+            items,
+            index,
+            owners,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = super::serialize(|| postcard::to_allocvec(&graph())).unwrap();
+        let (graph, unclaimed) = super::deserialize(|| postcard::from_bytes::<Graph>(&bytes));
+        let Graph {
+            tags,
+            items,
+            index,
+            mut owners,
+        } = graph.unwrap();
+        assert!(unclaimed.is_empty());
+        assert_eq!(vec!["x", "y", "z"], tags.values().collect::<Vec<_>>());
+        assert_eq!(2, items.iter().count());
+        assert_eq!(vec![("i".to_string(), 1)], index.iter().collect::<Vec<_>>());
+
+        // Dropping an item drops its tags, and the entry of the index is shared by both owners.
+        drop(owners.remove(0));
+        assert_eq!(vec!["z"], tags.values().collect::<Vec<_>>());
+        assert_eq!(1, items.iter().count());
+        assert!(index.contains_key("i"));
+        owners.clear();
+        assert_eq!(0, tags.iter().count());
+        assert!(index.is_empty());
+    }
+
+    #[derive(Deserialize)]
+    struct Listed(#[serde(with = "super::list")] LinkedList<String>, Vec<Tag>);
+
+    fn listed(json: &str) -> (Result<Listed, String>, super::Unclaimed) {
+        let (result, unclaimed) = super::deserialize(|| serde_json::from_str::<Listed>(json));
+        (result.map_err(|e| e.to_string()), unclaimed)
+    }
+
+    #[test]
+    fn errors() {
+        let list = LinkedList::<String>::new();
+        let handle = list.push_back("a".into());
+        let error = serde_json::to_string(&handle).unwrap_err();
+        assert_eq!(super::NO_SESSION, error.to_string());
+        let error = super::serialize(|| serde_json::to_string(&handle)).unwrap_err();
+        assert_eq!(super::UNKNOWN_ENTRY, error.to_string());
+
+        let (result, _) = super::deserialize(|| serde_json::from_str::<Tag>("0"));
+        let error = result.unwrap_err().to_string();
+        assert!(error.starts_with("Unknown entry id 0"), "{error}");
+
+        let error = listed(r#"[[[0,"a"],[0,"b"]],[]]"#).0.err().unwrap();
+        assert!(error.starts_with("Duplicate entry id 0"), "{error}");
+        let error = listed(r#"[[[0,"a"],[1,"b"]],[1,1]]"#).0.err().unwrap();
+        assert!(error.starts_with("The handle of entry 1 is"), "{error}");
+    }
+
+    #[test]
+    fn unclaimed() {
+        let (result, unclaimed) = listed(r#"[[[0,"a"],[1,"b"]],[1]]"#);
+        let Listed(list, _handles) = result.unwrap();
+        assert_eq!(1, unclaimed.len());
+        assert_eq!(vec!["a", "b"], list.values().collect::<Vec<_>>());
+        drop(unclaimed);
+        assert_eq!(vec!["b"], list.values().collect::<Vec<_>>());
+    }
+}
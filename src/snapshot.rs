@@ -0,0 +1,454 @@
+//! A compact binary format to persist lists and maps.
+//!
+//! A snapshot starts with a header: the magic number, the format version, the kind of container,
+//! the number of records, and a checksum of the header. Each record follows, as length-prefixed
+//! fields (the value, or the key then the value) and a checksum of the record. Integers are little
+//! endian, and checksums are CRC-32.
+//!
+//! Keys and values are encoded by a user-supplied [Codec].
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::num::TryFromIntError;
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use crate::hash_map;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use crate::hash_map::LinkedHashMap;
+use crate::list;
+use crate::list::LinkedList;
+use crate::list::NodeFactory;
+
+pub const MAGIC: [u8; 4] = *b"LNKS";
+pub const VERSION: u16 = 1;
+
+const LIST: u8 = 0;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+const MAP: u8 = 1;
+
+/// Encodes and decodes the keys or the values of a snapshot.
+pub trait Codec<T> {
+    fn encode(&self, value: &T, output: &mut Vec<u8>);
+    fn decode(&self, input: &[u8]) -> Result<T, CodecError>;
+}
+
+pub type CodecError = Box<dyn core::error::Error + Send + Sync>;
+
+/// Encodes strings as UTF-8.
+pub struct Utf8;
+
+/// Encodes byte vectors as is.
+pub struct Bytes;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The input ended before the end of the snapshot.
+    Truncated,
+
+    /// The input does not start with [MAGIC].
+    BadMagic,
+
+    /// The snapshot was written by an unknown version of the format.
+    UnsupportedVersion(u16),
+
+    /// The snapshot holds a list where a map was expected, or the opposite.
+    WrongKind,
+
+    /// The header, or the record at the given index, does not match its checksum.
+    ChecksumMismatch { record: Option<u64> },
+
+    /// The codec could not decode a field of the record at the given index.
+    Codec { record: u64, error: CodecError },
+
+    /// The input goes on after the last record.
+    TrailingBytes,
+}
+
+/// Appends a snapshot of `list` to `output`.
+pub fn write_list<V, F>(
+    list: &LinkedList<V, F>,
+    codec: &(impl Codec<V> + ?Sized),
+    output: &mut Vec<u8>,
+) where
+    F: NodeFactory<Value = V>,
+{
+    write_header(LIST, list.iter().count() as u64, output);
+    for value in list.iter() {
+        let start = output.len();
+        write_field(output, |output| codec.encode(&value, output));
+        write_checksum(start, output);
+    }
+}
+
+/// Reads a snapshot written by [write_list]. The values are owned by the returned handles.
+#[allow(clippy::type_complexity)]
+pub fn read_list<V, F>(
+    input: &[u8],
+    codec: &(impl Codec<V> + ?Sized),
+) -> Result<(LinkedList<V, F>, Vec<list::Handle<F>>), Error>
+where
+    F: NodeFactory<Value = V>,
+{
+    let mut reader = Reader::new(input);
+    let count = reader.header(LIST)?;
+    let list = LinkedList::new();
+    let mut handles = Vec::with_capacity(reader.capacity(count));
+    for record in 0..count {
+        let [value] = reader.record(record)?;
+        handles.push(list.push_back(decode(codec, value, record)?));
+    }
+    reader.end()?;
+    Ok((list, handles))
+}
+
+/// Appends a snapshot of `map` to `output`.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub fn write_map<K, V>(
+    map: &LinkedHashMap<K, V>,
+    key_codec: &(impl Codec<K> + ?Sized),
+    value_codec: &(impl Codec<V> + ?Sized),
+    output: &mut Vec<u8>,
+) where
+    K: Clone + Eq + core::hash::Hash,
+{
+    write_header(MAP, map.len() as u64, output);
+    map.for_each(|key, value| {
+        let start = output.len();
+        write_field(output, |output| key_codec.encode(key, output));
+        write_field(output, |output| value_codec.encode(value, output));
+        write_checksum(start, output);
+    });
+}
+
+/// Reads a snapshot written by [write_map]. The entries are owned by the returned handles.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[allow(clippy::type_complexity)]
+pub fn read_map<K, V>(
+    input: &[u8],
+    key_codec: &(impl Codec<K> + ?Sized),
+    value_codec: &(impl Codec<V> + ?Sized),
+) -> Result<(LinkedHashMap<K, V>, Vec<hash_map::Handle<K, V>>), Error>
+where
+    K: Clone + Eq + core::hash::Hash,
+{
+    let mut reader = Reader::new(input);
+    let count = reader.header(MAP)?;
+    let capacity = reader.capacity(count);
+    let map = LinkedHashMap::with_capacity(capacity);
+    let mut handles = Vec::with_capacity(capacity);
+    for record in 0..count {
+        let [key, value] = reader.record(record)?;
+        let key = decode(key_codec, key, record)?;
+        let value = decode(value_codec, value, record)?;
+        handles.push(map.insert(key, value).handle);
+    }
+    reader.end()?;
+    Ok((map, handles))
+}
+
+fn write_header(kind: u8, count: u64, output: &mut Vec<u8>) {
+    let start = output.len();
+    output.extend_from_slice(&MAGIC);
+    output.extend_from_slice(&VERSION.to_le_bytes());
+    output.push(kind);
+    output.extend_from_slice(&count.to_le_bytes());
+    write_checksum(start, output);
+}
+
+/// Writes the field produced by `encode`, prefixed by its length.
+pub(crate) fn write_field(output: &mut Vec<u8>, encode: impl FnOnce(&mut Vec<u8>)) {
+    try_write_field(output, encode).expect("Fields are at most 4 GiB")
+}
+
+/// Like [write_field], but fails instead of panicking if the field is longer than 4 GiB.
+pub(crate) fn try_write_field(
+    output: &mut Vec<u8>,
+    encode: impl FnOnce(&mut Vec<u8>),
+) -> Result<(), TryFromIntError> {
+    let start = output.len();
+    output.extend_from_slice(&[0; 4]);
+    encode(output);
+    let len = u32::try_from(output.len() - start - 4)?;
+    output[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+/// Writes the checksum of the bytes written since `start`.
+pub(crate) fn write_checksum(start: usize, output: &mut Vec<u8>) {
+    let checksum = crc32(&output[start..]);
+    output.extend_from_slice(&checksum.to_le_bytes());
+}
+
+pub(crate) struct Reader<'t> {
+    input: &'t [u8],
+}
+
+impl<'t> Reader<'t> {
+    pub(crate) fn new(input: &'t [u8]) -> Self {
+        Self { input }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    pub(crate) fn remaining(&self) -> &'t [u8] {
+        self.input
+    }
+
+    /// Returns the bytes read since `start`, a previous state of the input.
+    pub(crate) fn since(&self, start: &'t [u8]) -> &'t [u8] {
+        &start[..start.len() - self.input.len()]
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'t [u8], Error> {
+        if self.input.len() < len {
+            return Err(Error::Truncated);
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn take_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn checksum(&mut self, bytes: &[u8], record: Option<u64>) -> Result<(), Error> {
+        let checksum = u32::from_le_bytes(self.take_array()?);
+        if crc32(bytes) != checksum {
+            return Err(Error::ChecksumMismatch { record });
+        }
+        Ok(())
+    }
+
+    /// Reads the header and returns the number of records.
+    fn header(&mut self, kind: u8) -> Result<u64, Error> {
+        let start = self.remaining();
+        if self.take_array()? != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        let version = u16::from_le_bytes(self.take_array()?);
+        let [actual_kind] = self.take_array()?;
+        let count = u64::from_le_bytes(self.take_array()?);
+        self.checksum(self.since(start), None)?;
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        if actual_kind != kind {
+            return Err(Error::WrongKind);
+        }
+        Ok(count)
+    }
+
+    /// The number of records to reserve: each record takes at least 8 bytes.
+    fn capacity(&self, count: u64) -> usize {
+        (self.input.len() / 8).min(usize::try_from(count).unwrap_or(usize::MAX))
+    }
+
+    /// Reads the `N` fields of a record, once its checksum is verified.
+    fn record<const N: usize>(&mut self, record: u64) -> Result<[&'t [u8]; N], Error> {
+        let start = self.remaining();
+        let mut fields = [&[][..]; N];
+        for field in &mut fields {
+            *field = self.field()?;
+        }
+        self.checksum(self.since(start), Some(record))?;
+        Ok(fields)
+    }
+
+    /// Reads a length-prefixed field.
+    pub(crate) fn field(&mut self) -> Result<&'t [u8], Error> {
+        let len = u32::from_le_bytes(self.take_array()?);
+        self.take(len as usize)
+    }
+
+    fn end(&self) -> Result<(), Error> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(Error::TrailingBytes),
+        }
+    }
+}
+
+pub(crate) fn decode<T>(
+    codec: &(impl Codec<T> + ?Sized),
+    field: &[u8],
+    record: u64,
+) -> Result<T, Error> {
+    codec
+        .decode(field)
+        .map_err(|error| Error::Codec { record, error })
+}
+
+impl Codec<String> for Utf8 {
+    fn encode(&self, value: &String, output: &mut Vec<u8>) {
+        output.extend_from_slice(value.as_bytes())
+    }
+
+    fn decode(&self, input: &[u8]) -> Result<String, CodecError> {
+        Ok(String::from_utf8(input.to_vec())?)
+    }
+}
+
+impl Codec<Vec<u8>> for Bytes {
+    fn encode(&self, value: &Vec<u8>, output: &mut Vec<u8>) {
+        output.extend_from_slice(value)
+    }
+
+    fn decode(&self, input: &[u8]) -> Result<Vec<u8>, CodecError> {
+        Ok(input.to_vec())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "The snapshot is truncated"),
+            Error::BadMagic => write!(f, "The input is not a snapshot"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "Unsupported snapshot version {version}")
+            }
+            Error::WrongKind => write!(f, "The snapshot holds another kind of container"),
+            Error::ChecksumMismatch { record: None } => write!(f, "Corrupt snapshot header"),
+            Error::ChecksumMismatch {
+                record: Some(record),
+            } => write!(f, "Corrupt snapshot record {record}"),
+            Error::Codec { record, error } => {
+                write!(f, "Cannot decode snapshot record {record}: {error}")
+            }
+            Error::TrailingBytes => write!(f, "Unexpected bytes after the snapshot"),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Codec { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let crc = bytes.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn list() -> Vec<u8> {
+        let list = LinkedList::<String>::new();
+        let _handles = ["a", "bb", ""].map(|v| list.push_back(v.into()));
+        let mut output = vec![];
+        write_list(&list, &Utf8, &mut output);
+        output
+    }
+
+    #[test]
+    fn list_round_trip() {
+        let (list, handles) = read_list::<_, list::BoxNodeFactory<_>>(&list(), &Utf8).unwrap();
+        assert_eq!(3, handles.len());
+        let values = list.iter().map(|v| v.clone()).collect::<Vec<String>>();
+        assert_eq!(vec!["a", "bb", ""], values);
+        drop(handles);
+        assert_eq!(0, list.iter().count());
+    }
+
+    #[test]
+    #[cfg(any(feature = "std", feature = "hashbrown"))]
+    fn map_round_trip() {
+        let map = LinkedHashMap::<String, Vec<u8>>::new();
+        let _handles = [("b", vec![1, 2]), ("a", vec![]), ("c", vec![3])]
+            .map(|(k, v)| map.insert(k.into(), v).handle);
+        let mut output = vec![];
+        write_map(&map, &Utf8, &Bytes, &mut output);
+
+        let (read, handles) = read_map(&output, &Utf8, &Bytes).unwrap();
+        assert_eq!(map, read);
+        assert_eq!(3, handles.len());
+        assert!(matches!(
+            read_list::<_, list::BoxNodeFactory<_>>(&output, &Utf8),
+            Err(Error::WrongKind)
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        let read = |input: &[u8]| read_list::<_, list::BoxNodeFactory<_>>(input, &Utf8).err();
+        let snapshot = list();
+
+        for len in 0..snapshot.len() {
+            assert!(matches!(read(&snapshot[..len]), Some(Error::Truncated)));
+        }
+        let mut trailing = snapshot.clone();
+        trailing.push(0);
+        assert!(matches!(read(&trailing), Some(Error::TrailingBytes)));
+        assert!(matches!(read(b"JSON{}"), Some(Error::BadMagic)));
+
+        // Flips a bit of the second value, after the 19-byte header, the 9-byte first record and
+        // the length of the value.
+        let mut corrupt = snapshot.clone();
+        corrupt[19 + 9 + 4] ^= 1;
+        let error = read(&corrupt).unwrap();
+        assert!(matches!(error, Error::ChecksumMismatch { record: Some(1) }));
+        assert_eq!("Corrupt snapshot record 1", error.to_string());
+
+        // The header is checked before its version.
+        let mut version = snapshot.clone();
+        version[4] = 2;
+        assert!(matches!(
+            read(&version),
+            Some(Error::ChecksumMismatch { record: None })
+        ));
+        let checksum = crc32(&version[..15]);
+        version[15..19].copy_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(read(&version), Some(Error::UnsupportedVersion(2))));
+    }
+
+    #[test]
+    fn codec_error() {
+        let list = LinkedList::<Vec<u8>>::new();
+        let _handle = list.push_back(vec![0xFF]);
+        let mut output = vec![];
+        write_list(&list, &Bytes, &mut output);
+        let error = read_list::<String, list::BoxNodeFactory<_>>(&output, &Utf8).unwrap_err();
+        assert!(matches!(error, Error::Codec { record: 0, .. }));
+        assert!(core::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(0xCBF43926, crc32(b"123456789"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Thread-safe variants of the containers.
+//!
+//! The containers are built on `Rc` and `RefCell`, which cannot cross threads. The variants here
+//! wrap them, and their handles, so that every access goes through a lock shared by the container
+//! and all its handles. The reference counts and cells are therefore never touched by two threads
+//! at once, and a handle can be dropped on any thread.
+//!
+//! The lock is reentrant: callbacks run while it is held, and may use the container again.
+
+use std::mem::ManuallyDrop;
+#[cfg(not(loom))]
+use std::sync::Arc;
+
+#[cfg(loom)]
+use ::loom::sync::Arc;
+#[cfg(not(loom))]
+use parking_lot::ReentrantMutex;
+
+#[cfg(loom)]
+use self::loom::ReentrantMutex;
+
+pub mod hash_map;
+pub mod list;
+
+#[cfg(loom)]
+mod loom;
+
+#[cfg(not(loom))]
+type Lock = ReentrantMutex<()>;
+
+#[cfg(loom)]
+type Lock = ReentrantMutex;
+
+/// Confines a value built on `Rc`, so that it is only used while `lock` is held.
+///
+/// Values confined by the same lock may share reference-counted state.
+struct Confined<T> {
+    lock: Arc<Lock>,
+    value: ManuallyDrop<T>,
+}
+
+impl<T> Confined<T> {
+    fn new(value: T) -> Self {
+        Self {
+            lock: Default::default(),
+            value: ManuallyDrop::new(value),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let _guard = self.lock.lock();
+        f(&self.value)
+    }
+
+    /// Confines the result of `f` by the same lock.
+    fn confine<U>(&self, f: impl FnOnce(&T) -> U) -> Confined<U> {
+        Confined {
+            lock: self.lock.clone(),
+            value: ManuallyDrop::new(self.with(f)),
+        }
+    }
+}
+
+impl<T> Drop for Confined<T> {
+    fn drop(&mut self) {
+        let _guard = self.lock.lock();
+        // SAFETY: `self.value` is never used again.
+        unsafe { ManuallyDrop::drop(&mut self.value) }
+    }
+}
+
+impl<T: Clone> Clone for Confined<T> {
+    fn clone(&self) -> Self {
+        self.confine(T::clone)
+    }
+}
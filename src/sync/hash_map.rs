@@ -0,0 +1,290 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::hash::Hash;
+
+use super::Confined;
+use crate::hash_map;
+use crate::hash_map::subscription;
+use crate::hash_map::subscription::Event;
+
+/// A thread-safe [LinkedHashMap](crate::hash_map::LinkedHashMap).
+///
+/// Clones share the same map.
+pub struct LinkedHashMap<K, V>(Confined<hash_map::LinkedHashMap<K, V>>)
+where
+    K: Clone + Eq + Hash;
+
+/// A thread-safe [Handle](crate::hash_map::Handle): dropping the last clone on any thread removes
+/// its entry.
+#[derive(Clone)]
+pub struct Handle<K, V>(#[allow(unused)] Confined<hash_map::Handle<K, V>>)
+where
+    K: Clone + Eq + Hash;
+
+/// A thread-safe [Subscription](crate::hash_map::subscription::Subscription).
+#[must_use]
+pub struct Subscription<K, V>(#[allow(unused)] Confined<subscription::Subscription<K, V>>);
+
+#[must_use]
+pub struct InsertResult<K, V, P>
+where
+    K: Clone + Eq + Hash,
+{
+    pub previous: P,
+    pub handle: Handle<K, V>,
+}
+
+// SAFETY: the map, its handles and its subscriptions are only used while their shared lock is
+// held.
+unsafe impl<K: Clone + Eq + Hash + Send, V: Send> Send for LinkedHashMap<K, V> {}
+unsafe impl<K: Clone + Eq + Hash + Send, V: Send> Sync for LinkedHashMap<K, V> {}
+unsafe impl<K: Clone + Eq + Hash + Send, V: Send> Send for Handle<K, V> {}
+unsafe impl<K: Clone + Eq + Hash + Send, V: Send> Sync for Handle<K, V> {}
+unsafe impl<K: Send, V: Send> Send for Subscription<K, V> {}
+unsafe impl<K: Send, V: Send> Sync for Subscription<K, V> {}
+
+impl<K, V> LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    pub fn new() -> Self {
+        Self(Confined::new(hash_map::LinkedHashMap::new()))
+    }
+
+    pub fn insert(&self, key: K, value: V) -> InsertResult<K, V, Option<V>> {
+        self.insert_impl(|map| map.insert(key, value))
+    }
+
+    /// See [LinkedHashMap::insert_with_on_remove](crate::hash_map::LinkedHashMap::insert_with_on_remove).
+    ///
+    /// `on_remove` runs on the thread that removes the entry, while the map is locked.
+    pub fn insert_with_on_remove(
+        &self,
+        key: K,
+        value: V,
+        on_remove: impl FnOnce(K, V) + Send + 'static,
+    ) -> InsertResult<K, V, Option<V>> {
+        self.insert_impl(|map| map.insert_with_on_remove(key, value, on_remove))
+    }
+
+    fn insert_impl(
+        &self,
+        insert: impl FnOnce(&hash_map::LinkedHashMap<K, V>) -> hash_map::InsertResult<K, V, Option<V>>,
+    ) -> InsertResult<K, V, Option<V>> {
+        let mut previous = None;
+        let handle = self.0.confine(|map| {
+            let result = insert(map);
+            previous = result.previous;
+            result.handle
+        });
+        InsertResult {
+            previous,
+            handle: Handle(handle),
+        }
+    }
+
+    /// See [LinkedHashMap::subscribe](crate::hash_map::LinkedHashMap::subscribe).
+    ///
+    /// `callback` runs on the thread that changes the map, while the map is locked.
+    pub fn subscribe(
+        &self,
+        callback: impl Fn(&Event<K, V>) + Send + 'static,
+    ) -> Subscription<K, V> {
+        Subscription(self.0.confine(|map| map.subscribe(callback)))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.0.with(|map| map.contains_key(key))
+    }
+
+    /// See [LinkedHashMap::retain](crate::hash_map::LinkedHashMap::retain).
+    ///
+    /// `keep` runs while the map is locked.
+    pub fn retain(&self, keep: impl FnMut(&K, &mut V) -> bool) {
+        self.0.with(|map| map.retain(keep))
+    }
+
+    pub fn clear(&self) {
+        self.0.with(|map| map.clear())
+    }
+
+    pub fn drain(&self) -> impl Iterator<Item = (K, V)> {
+        self.0
+            .with(|map| map.drain().collect::<Vec<_>>())
+            .into_iter()
+    }
+
+    pub fn sort_by(&self, compare: impl FnMut((&K, &V), (&K, &V)) -> Ordering) {
+        self.0.with(|map| map.sort_by(compare))
+    }
+
+    pub fn sort_keys(&self)
+    where
+        K: Ord,
+    {
+        self.0.with(|map| map.sort_keys())
+    }
+}
+
+impl<K, V> LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.0.with(|map| map.get(key))
+    }
+
+    /// Copies of the keys, taken while the map is locked.
+    pub fn keys(&self) -> impl Iterator<Item = K> {
+        self.0
+            .with(|map| map.keys().collect::<Vec<_>>())
+            .into_iter()
+    }
+
+    /// Copies of the values, taken while the map is locked.
+    pub fn values(&self) -> impl Iterator<Item = V> {
+        self.0
+            .with(|map| map.values().collect::<Vec<_>>())
+            .into_iter()
+    }
+
+    /// Copies of the entries, taken while the map is locked.
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> {
+        self.0
+            .with(|map| map.iter().collect::<Vec<_>>())
+            .into_iter()
+    }
+
+    pub fn first(&self) -> Option<(K, V)> {
+        self.0.with(|map| map.first())
+    }
+
+    pub fn last(&self) -> Option<(K, V)> {
+        self.0.with(|map| map.last())
+    }
+}
+
+impl<K, V> Default for LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Clone for LinkedHashMap<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V> std::fmt::Debug for LinkedHashMap<K, V>
+where
+    K: std::fmt::Debug + Clone + Eq + Hash,
+    V: std::fmt::Debug + Clone,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.with(|map| map.fmt(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use super::LinkedHashMap;
+    use crate::hash_map::subscription::Event;
+
+    #[test]
+    fn threads() {
+        let map = LinkedHashMap::<String, i32>::new();
+        let (sender, receiver) = mpsc::channel();
+        let threads = (0..4)
+            .map(|t| {
+                let map = map.clone();
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        let key = format!("{t}-{i}");
+                        sender.send(map.insert(key, i).handle).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(sender);
+        threads.into_iter().for_each(|t| t.join().unwrap());
+
+        let handles = receiver.into_iter().collect::<Vec<_>>();
+        assert_eq!(400, map.keys().count());
+        assert_eq!(Some(7), map.get("2-7"));
+
+        // Handles remove their entry when their last clone is dropped on another thread.
+        let clones = handles.clone();
+        std::thread::spawn(move || drop(handles)).join().unwrap();
+        assert_eq!(400, map.keys().count());
+        std::thread::spawn(move || drop(clones)).join().unwrap();
+        assert_eq!(0, map.keys().count());
+    }
+
+    #[test]
+    fn subscribe() {
+        let map = LinkedHashMap::<String, i32>::new();
+        let events = Arc::new(Mutex::new(vec![]));
+        let _subscription = map.subscribe({
+            let events = events.clone();
+            let map = map.clone();
+            move |event| {
+                // Callbacks may use the map, which is still locked by this thread.
+                let len = map.keys().count();
+                events.lock().unwrap().push((event.clone(), len));
+            }
+        });
+
+        let a = map.insert("a".into(), 1).handle;
+        std::thread::spawn(move || drop(a)).join().unwrap();
+        assert_eq!(
+            vec![
+                (Event::Inserted { key: "a".into() }, 1),
+                (
+                    Event::Removed {
+                        key: "a".into(),
+                        value: 1
+                    },
+                    0
+                ),
+            ],
+            *events.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn on_remove() {
+        let map = LinkedHashMap::<String, i32>::new();
+        let removed = Arc::new(Mutex::new(vec![]));
+        let a = map
+            .insert_with_on_remove("a".into(), 1, {
+                let removed = removed.clone();
+                move |k, v| removed.lock().unwrap().push((k, v))
+            })
+            .handle;
+        assert!(map.contains_key("a"));
+        std::thread::spawn(move || drop(a)).join().unwrap();
+        assert!(!map.contains_key("a"));
+        assert_eq!(vec![("a".to_string(), 1)], *removed.lock().unwrap());
+    }
+}
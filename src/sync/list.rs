@@ -0,0 +1,168 @@
+use std::cmp::Ordering;
+
+use super::Confined;
+use crate::list;
+use crate::list::BoxNodeFactory;
+
+/// A thread-safe [LinkedList](crate::list::LinkedList).
+///
+/// Clones share the same list.
+pub struct LinkedList<V>(Confined<list::LinkedList<V>>);
+
+/// A thread-safe [Handle](crate::list::Handle): dropping it on any thread unlinks its node.
+#[must_use]
+pub struct Handle<V>(Confined<list::Handle<BoxNodeFactory<V>>>);
+
+// SAFETY: the list and its handles are only used while their shared lock is held.
+unsafe impl<V: Send> Send for LinkedList<V> {}
+unsafe impl<V: Send> Sync for LinkedList<V> {}
+unsafe impl<V: Send> Send for Handle<V> {}
+unsafe impl<V: Send> Sync for Handle<V> {}
+
+impl<V> LinkedList<V> {
+    pub fn new() -> Self {
+        Self(Confined::new(list::LinkedList::new()))
+    }
+
+    pub fn push_back(&self, value: V) -> Handle<V> {
+        Handle(self.0.confine(|list| list.push_back(value)))
+    }
+
+    /// See [LinkedList::push_back_with_on_remove](crate::list::LinkedList::push_back_with_on_remove).
+    ///
+    /// `on_remove` runs on the thread that drops the handle.
+    pub fn push_back_with_on_remove(
+        &self,
+        value: V,
+        on_remove: impl FnOnce(V) + Send + 'static,
+    ) -> Handle<V> {
+        Handle(
+            self.0
+                .confine(|list| list.push_back_with_on_remove(value, on_remove)),
+        )
+    }
+
+    /// See [LinkedList::retain](crate::list::LinkedList::retain).
+    ///
+    /// `keep` runs while the list is locked.
+    pub fn retain(&self, keep: impl FnMut(&V) -> bool) {
+        self.0.with(|list| list.retain(keep))
+    }
+
+    pub fn clear(&self) {
+        self.0.with(|list| list.clear())
+    }
+
+    pub fn sort_by(&self, compare: impl FnMut(&V, &V) -> Ordering) {
+        self.0.with(|list| list.sort_by(compare))
+    }
+
+    pub fn reverse(&self) {
+        self.0.with(|list| list.reverse())
+    }
+
+    /// Copies of the values, taken while the list is locked.
+    pub fn values(&self) -> impl Iterator<Item = V>
+    where
+        V: Clone,
+    {
+        self.0
+            .with(|list| list.values().collect::<Vec<_>>())
+            .into_iter()
+    }
+}
+
+impl<V> Handle<V> {
+    /// The value is read without the lock, possibly while other threads read it through the
+    /// list, hence `V: Sync`.
+    pub fn value(&self) -> &V
+    where
+        V: Sync,
+    {
+        let value = self.0.with(|handle| handle.value() as *const V);
+        // SAFETY: the value is owned by the handle and never modified, only the links of the node
+        // are.
+        unsafe { &*value }
+    }
+}
+
+impl<V> Default for LinkedList<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Clone for LinkedList<V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<V: std::fmt::Debug> std::fmt::Debug for LinkedList<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.with(|list| list.fmt(f))
+    }
+}
+
+impl<V: std::fmt::Debug> std::fmt::Debug for Handle<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.with(|handle| handle.fmt(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use super::LinkedList;
+
+    #[test]
+    fn threads() {
+        let list = LinkedList::<i32>::new();
+        let (sender, receiver) = mpsc::channel();
+        let threads = (0..4)
+            .map(|t| {
+                let list = list.clone();
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        sender.send(list.push_back(t * 100 + i)).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(sender);
+        threads.into_iter().for_each(|t| t.join().unwrap());
+
+        let mut handles = receiver.into_iter().collect::<Vec<_>>();
+        assert_eq!(400, list.values().count());
+        list.sort_by(i32::cmp);
+        assert_eq!(
+            (0..400).collect::<Vec<_>>(),
+            list.values().collect::<Vec<_>>()
+        );
+
+        // Handles unlink their node when dropped on another thread.
+        let dropped = handles.split_off(200);
+        let dropped_values = dropped.iter().map(|h| *h.value()).collect::<Vec<_>>();
+        std::thread::spawn(move || drop(dropped)).join().unwrap();
+        assert_eq!(200, list.values().count());
+        assert!(list.values().all(|v| !dropped_values.contains(&v)));
+    }
+
+    #[test]
+    fn on_remove() {
+        let list = LinkedList::<String>::new();
+        let removed = Arc::new(Mutex::new(vec![]));
+        let handle = list.push_back_with_on_remove("a".into(), {
+            let removed = removed.clone();
+            move |v| removed.lock().unwrap().push(v)
+        });
+        assert_eq!("a", handle.value());
+        std::thread::spawn(move || drop(handle)).join().unwrap();
+        assert_eq!(vec!["a"], *removed.lock().unwrap());
+        assert_eq!("[]", format!("{list:?}"));
+    }
+}
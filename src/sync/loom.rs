@@ -0,0 +1,45 @@
+//! A reentrant mutex built on `loom` primitives, so that model checking explores the interleavings
+//! of [Confined](super::Confined) values.
+
+use loom::sync::Condvar;
+use loom::sync::Mutex;
+use loom::thread::ThreadId;
+
+#[derive(Default)]
+pub(super) struct ReentrantMutex {
+    /// The thread that holds the lock, and how many times it locked it.
+    owner: Mutex<Option<(ThreadId, usize)>>,
+    unlocked: Condvar,
+}
+
+pub(super) struct ReentrantMutexGuard<'t>(&'t ReentrantMutex);
+
+impl ReentrantMutex {
+    pub(super) fn lock(&self) -> ReentrantMutexGuard<'_> {
+        let me = loom::thread::current().id();
+        let mut owner = self.owner.lock().unwrap();
+        loop {
+            match &mut *owner {
+                None => *owner = Some((me, 1)),
+                Some((thread, count)) if *thread == me => *count += 1,
+                Some(_) => {
+                    owner = self.unlocked.wait(owner).unwrap();
+                    continue;
+                }
+            }
+            return ReentrantMutexGuard(self);
+        }
+    }
+}
+
+impl Drop for ReentrantMutexGuard<'_> {
+    fn drop(&mut self) {
+        let mut owner = self.0.owner.lock().unwrap();
+        let (_, count) = owner.as_mut().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            *owner = None;
+            self.0.unlocked.notify_one();
+        }
+    }
+}